@@ -0,0 +1,32 @@
+use std::env;
+use std::process::Command;
+
+/// Embed build metadata as `LONG_VERSION` unless it is given by the environment like `make`.
+fn main() {
+    println!("cargo:rerun-if-env-changed=LONG_VERSION");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+
+    let long_version = match env::var("LONG_VERSION") {
+        Ok(x) => x,
+        Err(_) => {
+            let version = env::var("CARGO_PKG_VERSION").unwrap();
+            let revision = Command::new("git")
+                .args(["log", "-1", "--format=%h %cd", "--date=short"])
+                .output()
+                .ok()
+                .filter(|x| x.status.success())
+                .and_then(|x| String::from_utf8(x.stdout).ok())
+                .map(|x| String::from(x.trim()))
+                .filter(|x| !x.is_empty());
+            match revision {
+                Some(x) => {
+                    let (rev, date) = x.split_at(x.find(' ').unwrap_or(x.len()));
+                    format!("{} ( rev: {}, date: {} )", version, rev, date.trim())
+                }
+                None => version,
+            }
+        }
+    };
+    println!("cargo:rustc-env=LONG_VERSION={}", long_version);
+}
@@ -1,321 +1,3178 @@
-use crate::config::Config;
-use log::debug;
-use std::collections::HashMap;
+use crate::completion;
+use crate::config::{Config, ConfigOption, ConfigVerilog, DefaultRules, LintOn, Mode, TextSync};
+use crate::define;
+use crate::folding;
+use crate::format;
+use crate::link;
+use crate::lint::{self, sort_diagnostics, Parsed};
+use crate::message::{self, Message};
+use crate::position::{get_line_end, get_offset, get_position};
+use crate::reference;
+use crate::selection;
+use crate::semantic;
+use crate::symbol;
+use log::{debug, error, warn};
+use simplelog::{LevelFilter, WriteLogger};
+use std::collections::{HashMap, HashSet};
 use std::default::Default;
 use std::env;
+use std::fs::File;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
-use sv_parser::{parse_sv_str, Define, DefineText};
+use std::time::{Duration, Instant};
+use sv_parser::{Defines, SyntaxTree};
 use svlint::config::Config as LintConfig;
 use svlint::linter::Linter;
-use tower_lsp::jsonrpc::Result;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinHandle;
+use tower_lsp::jsonrpc::{Error, Result};
+use tower_lsp::lsp_types::notification::Progress;
+use tower_lsp::lsp_types::request::WorkDoneProgressCreate;
 use tower_lsp::lsp_types::*;
 use tower_lsp::{async_trait, Client, LanguageServer};
 
+/// Command to lint all SystemVerilog files under the workspace folders.
+const LINT_WORKSPACE: &str = "svls.lintWorkspace";
+
+/// Token of the progress while loading the configuration at startup.
+const LOAD_CONFIGURATION: &str = "svls.loadConfiguration";
+
+/// Command to lint all open documents again.
+const RELINT: &str = "svls.relint";
+
+/// Command to reload `.svls.toml` and `.svlint.toml`.
+const RELOAD: &str = "svls.reload";
+
+/// Command to get the status of the server.
+const STATUS: &str = "svls.status";
+
+/// Command to get the configuration in effect.
+const DUMP_CONFIG: &str = "svls.dumpConfig";
+
+#[derive(Clone)]
 pub struct Backend {
     client: Client,
+    config_svls: Option<PathBuf>,
+    config_svlint: Option<PathBuf>,
+    /// Defines and include paths given by the command line.
+    verilog: ConfigVerilog,
+    capabilities: Arc<RwLock<ClientCapabilities>>,
+    text_sync: Arc<RwLock<TextSync>>,
+    lint_pool: Arc<RwLock<Arc<Semaphore>>>,
+    /// All permits of `lint_pool` held until the linters are loaded at startup.
+    loading: Arc<RwLock<Option<OwnedSemaphorePermit>>>,
     root_uri: Arc<RwLock<Option<Url>>>,
-    config: Arc<RwLock<Option<Config>>>,
-    linter: Arc<RwLock<Option<Linter>>>,
+    /// Locale of the client given by `initialize`.
+    locale: Arc<RwLock<Option<String>>>,
+    workspaces: Arc<RwLock<Vec<Arc<Workspace>>>>,
+    settings: Arc<RwLock<Option<serde_json::Value>>>,
+    documents: Arc<RwLock<HashMap<Url, Document>>>,
+    syntax_trees: Arc<RwLock<HashMap<Url, VersionedSyntaxTree>>>,
+    last_diagnostics: Arc<RwLock<HashMap<Url, Vec<Diagnostic>>>>,
+    missing_include_paths: Arc<RwLock<HashSet<PathBuf>>>,
+    /// Top-level declarations of files on disk, for workspace symbols.
+    symbols: Arc<RwLock<HashMap<Url, Vec<SymbolInformation>>>>,
+    pending: Arc<RwLock<HashMap<Url, JoinHandle<()>>>>,
+    /// Document version of the diagnostics last published for each URI.
+    published: Arc<RwLock<HashMap<Url, i32>>>,
 }
 
-impl Backend {
-    pub fn new(client: Client) -> Self {
-        Backend {
-            client,
-            root_uri: Default::default(),
-            config: Default::default(),
-            linter: Default::default(),
+/// Syntax tree and the document version it was parsed from.
+type VersionedSyntaxTree = (i32, Arc<SyntaxTree>);
+
+#[derive(Default)]
+struct Document {
+    version: i32,
+    text: String,
+    included: Vec<Url>,
+}
+
+/// Configuration and linter of a workspace folder.
+struct Workspace {
+    root_uri: Option<Url>,
+    config: RwLock<Config>,
+    linter: RwLock<Option<Linter>>,
+    /// Defines after each file of `option.compilation_order`, computed on demand.
+    unit_defines: RwLock<Vec<Defines>>,
+    /// Locale of messages generated by svls.
+    locale: Option<String>,
+}
+
+impl Workspace {
+    /// Create a workspace without root by `config` for tests.
+    #[cfg(test)]
+    fn with_config(config: Config) -> Workspace {
+        Workspace {
+            root_uri: None,
+            config: RwLock::new(config),
+            linter: RwLock::new(None),
+            unit_defines: Default::default(),
+            locale: None,
         }
     }
 
-    fn lint(&self, s: &str) -> Vec<Diagnostic> {
-        let mut ret = Vec::new();
-
-        let root_uri = self.root_uri.read().unwrap();
-        let root_uri = if let Some(ref root_uri) = *root_uri {
-            if let Ok(root_uri) = root_uri.to_file_path() {
-                root_uri
-            } else {
-                PathBuf::from("")
-            }
-        } else {
-            PathBuf::from("")
-        };
+    /// Get the include paths of the config, resolved against the workspace root.
+    fn include_paths(&self) -> Vec<PathBuf> {
+        let root_uri = self
+            .root_uri
+            .as_ref()
+            .and_then(to_file_path)
+            .unwrap_or_default();
 
         let config = self.config.read().unwrap();
         let mut include_paths = Vec::new();
-        let mut defines = HashMap::new();
-        if let Some(ref config) = *config {
-            for path in &config.verilog.include_paths {
-                let mut p = root_uri.clone();
-                p.push(PathBuf::from(path));
-                include_paths.push(p);
-            }
-            for define in &config.verilog.defines {
-                let mut define = define.splitn(2, '=');
-                let ident = String::from(define.next().unwrap());
-                let text = if let Some(x) = define.next() {
-                    if let Ok(x) = enquote::unescape(x, None) {
-                        Some(DefineText::new(x, None))
-                    } else {
-                        None
+        for path in &config.verilog.include_paths {
+            let mut p = root_uri.clone();
+            p.push(PathBuf::from(path));
+            if is_glob(path) {
+                for p in glob::glob(&p.to_string_lossy())
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                {
+                    if p.is_dir() && !include_paths.contains(&p) {
+                        include_paths.push(p);
                     }
-                } else {
-                    None
-                };
-                let define = Define::new(ident.clone(), vec![], text);
-                defines.insert(ident, Some(define));
+                }
+            } else if !include_paths.contains(&p) {
+                include_paths.push(p);
             }
+        }
+        include_paths
+    }
+
+    /// Check whether `uri` matches an exclude pattern of the config, or doesn't match any include
+    /// pattern if they are given, relative to the workspace root.
+    fn is_excluded(&self, uri: &Url) -> bool {
+        let path = match to_file_path(uri) {
+            Some(x) => x,
+            None => return false,
+        };
+        let root = self.root_uri.as_ref().and_then(to_file_path);
+        let path = match root {
+            Some(ref root) => path.strip_prefix(root).unwrap_or(&path),
+            None => &path,
+        };
+        let config = self.config.read().unwrap();
+        let matches = |patterns: &[String]| {
+            patterns.iter().any(|x| match glob::Pattern::new(x) {
+                Ok(pattern) => pattern.matches_path(path),
+                Err(_) => false,
+            })
+        };
+        let include_patterns = &config.option.include_patterns;
+        let included = include_patterns.is_empty() || matches(include_patterns);
+        !included || matches(&config.option.exclude)
+    }
+
+    /// Parse `s` as the content of `uri`.
+    ///
+    /// The directory of `uri` is searched for included files before the include paths of the config.
+    fn parse(&self, uri: &Url, s: &str) -> Parsed {
+        let path = to_file_path(uri);
+        let include_paths = self.document_include_paths(path.as_deref());
+        let defines = match path {
+            Some(ref path) => self.unit_defines(path),
+            None => None,
+        };
+        let config = self.config.read().unwrap();
+        match defines {
+            Some(defines) => lint::parse_with_defines(s, &defines, &include_paths, &config.option),
+            None => lint::parse(s, &config, &include_paths),
+        }
+    }
+
+    /// Get the include paths for the document at `path`, starting from its directory.
+    fn document_include_paths(&self, path: Option<&Path>) -> Vec<PathBuf> {
+        let mut include_paths = self.include_paths();
+        if let Some(dir) = path.and_then(Path::parent) {
+            include_paths.retain(|x| x != dir);
+            include_paths.insert(0, dir.to_path_buf());
+        }
+        include_paths
+    }
+
+    /// Get the defines visible from `path` if it is in `option.compilation_order`.
+    ///
+    /// They are the defines of the config plus macros defined by the preceding files on disk.
+    fn unit_defines(&self, path: &Path) -> Option<Defines> {
+        let root = self
+            .root_uri
+            .as_ref()
+            .and_then(to_file_path)
+            .unwrap_or_default();
+        let order: Vec<_> = {
+            let config = self.config.read().unwrap();
+            let order = &config.option.compilation_order;
+            order.iter().map(|x| root.join(x)).collect()
         };
-        debug!("include_paths: {:?}", include_paths);
-        debug!("defines: {:?}", defines);
+        let index = order.iter().position(|x| x == path)?;
+
+        let mut cache = self.unit_defines.write().unwrap();
+        while cache.len() < index {
+            let mut defines = match cache.last() {
+                Some(x) => x.clone(),
+                None => lint::get_defines(&self.config.read().unwrap()),
+            };
+            let file = &order[cache.len()];
+            if let Ok(text) = std::fs::read_to_string(file) {
+                let include_paths = self.document_include_paths(Some(file));
+                let option = &self.config.read().unwrap().option;
+                if let Ok((_, x)) =
+                    lint::parse_with_defines(&text, &defines, &include_paths, option)
+                {
+                    defines = x;
+                }
+            }
+            cache.push(defines);
+        }
+        match index {
+            0 => Some(lint::get_defines(&self.config.read().unwrap())),
+            _ => Some(cache[index - 1].clone()),
+        }
+    }
+
+    /// Forget the defines of `option.compilation_order` to recompute them.
+    fn clear_unit_defines(&self) {
+        self.unit_defines.write().unwrap().clear();
+    }
 
-        let parsed = parse_sv_str(
+    /// Lint `s` as the content of `uri`, which is parsed to `parsed`.
+    fn lint(&self, uri: &Url, s: &str, parsed: &Parsed) -> HashMap<Url, Vec<Diagnostic>> {
+        let config = self.config.read().unwrap();
+        let mut linter = self.linter.write().unwrap();
+        lint::lint(
+            uri,
             s,
-            &PathBuf::from(""),
-            &defines,
-            &include_paths,
-            false,
-            false,
+            parsed,
+            &config,
+            linter.as_mut(),
+            self.locale.as_deref(),
+        )
+    }
+
+    /// Parse and lint `s` as the content of `uri`, logging the time taken by each.
+    ///
+    /// Lint rules are not checked unless `rules`, which reports parse errors only.
+    fn parse_and_lint(
+        &self,
+        uri: &Url,
+        s: &str,
+        rules: bool,
+    ) -> (Parsed, HashMap<Url, Vec<Diagnostic>>) {
+        let start = Instant::now();
+        let parsed = self.parse(uri, s);
+        let parse_time = start.elapsed();
+        let start = Instant::now();
+        let ret = if rules {
+            self.lint(uri, s, &parsed)
+        } else {
+            let config = self.config.read().unwrap();
+            lint::lint(uri, s, &parsed, &config, None, self.locale.as_deref())
+        };
+        let lint_time = start.elapsed();
+        debug!(
+            "lint_stat: {} parse: {:?} lint: {:?} diagnostics: {}",
+            uri,
+            parse_time,
+            lint_time,
+            ret.values().map(Vec::len).sum::<usize>()
+        );
+        (parsed, ret)
+    }
+
+    /// Apply `option.max_diagnostics_per_file` to `diags` of each file.
+    fn limit_diagnostics(&self, diags: &mut HashMap<Url, Vec<Diagnostic>>) {
+        let config = self.config.read().unwrap();
+        lint::limit_file_diagnostics(diags, &config, self.locale.as_deref());
+    }
+
+    /// Get the diagnostic reporting that `s` is not linted because it exceeds `option.max_file_bytes`.
+    fn size_diagnostic(&self, s: &str) -> Option<Diagnostic> {
+        let max = self.config.read().unwrap().option.max_file_bytes;
+        if s.len() <= max {
+            return None;
+        }
+        let message = message::get(
+            self.locale.as_deref(),
+            Message::FileTooLarge,
+            &[&s.len().to_string(), &max.to_string()],
         );
+        Some(Diagnostic::new(
+            Range::default(),
+            Some(DiagnosticSeverity::Information),
+            None,
+            Some(String::from("svls")),
+            message,
+            None,
+            None,
+        ))
+    }
+
+    /// Search `config` from the workspace root, or the current directory if the root is unknown.
+    fn search_config(&self, config: &Path) -> Option<PathBuf> {
+        let origin = self.root_uri.as_ref().and_then(to_file_path);
+        search_config(origin.as_deref(), config)
+    }
+}
+
+impl Backend {
+    pub fn new(
+        client: Client,
+        config_svls: Option<PathBuf>,
+        config_svlint: Option<PathBuf>,
+        verilog: ConfigVerilog,
+    ) -> Self {
+        Backend {
+            client,
+            config_svls,
+            config_svlint,
+            verilog,
+            capabilities: Default::default(),
+            text_sync: Default::default(),
+            lint_pool: Arc::new(RwLock::new(Arc::new(Semaphore::new(lint_threads(None))))),
+            loading: Default::default(),
+            root_uri: Default::default(),
+            locale: Default::default(),
+            workspaces: Default::default(),
+            settings: Default::default(),
+            documents: Default::default(),
+            syntax_trees: Default::default(),
+            last_diagnostics: Default::default(),
+            missing_include_paths: Default::default(),
+            symbols: Default::default(),
+            pending: Default::default(),
+            published: Default::default(),
+        }
+    }
+
+    /// Get `.svls.toml` given by the command line, or search it from the root of `workspace`.
+    fn config_svls(&self, workspace: &Workspace) -> Option<PathBuf> {
+        self.config_svls
+            .clone()
+            .or_else(|| workspace.search_config(&PathBuf::from(".svls.toml")))
+    }
+
+    /// Get `.svlint.toml` given by the command line, or search it from the root of `workspace`.
+    fn config_svlint(&self, workspace: &Workspace) -> Option<PathBuf> {
+        self.config_svlint
+            .clone()
+            .or_else(|| workspace.search_config(&PathBuf::from(".svlint.toml")))
+    }
+
+    /// Lint `s` as the content of `uri` with the workspace it belongs to.
+    ///
+    /// The syntax tree is cached for `version` of the document.
+    /// If `s` fails to parse, the diagnostics of the last successful parse are kept with the parse error.
+    /// Lint rules are checked only if `rules`.
+    fn lint(&self, uri: &Url, version: i32, s: &str, rules: bool) -> HashMap<Url, Vec<Diagnostic>> {
+        #[cfg(test)]
+        if s.contains(lint::TASK_PANIC_MARKER) {
+            panic!("{}", lint::TASK_PANIC_MARKER);
+        }
+        let workspace = match self.workspace(uri) {
+            Some(x) => x,
+            None => return vec![(uri.clone(), Vec::new())].into_iter().collect(),
+        };
+        if workspace.is_excluded(uri) {
+            debug!("excluded: {}", uri);
+            return vec![(uri.clone(), Vec::new())].into_iter().collect();
+        }
+        if !workspace.config.read().unwrap().option.diagnostics {
+            return vec![(uri.clone(), Vec::new())].into_iter().collect();
+        }
+        if let Some(x) = workspace.size_diagnostic(s) {
+            debug!("too_large: {}", uri);
+            return vec![(uri.clone(), vec![x])].into_iter().collect();
+        }
+        let (parsed, mut ret) = workspace.parse_and_lint(uri, s, rules);
         match parsed {
-            Ok((syntax_tree, _new_defines)) => {
-                let mut linter = self.linter.write().unwrap();
-                if let Some(ref mut linter) = *linter {
-                    for event in syntax_tree.into_iter().event() {
-                        for failed in linter.check(&syntax_tree, &event) {
-                            debug!("{:?}", failed);
-                            if failed.path != PathBuf::from("") {
-                                continue;
-                            }
-                            let (line, col) = get_position(s, failed.beg);
-                            ret.push(Diagnostic::new(
-                                Range::new(
-                                    Position::new(line, col),
-                                    Position::new(line, col + failed.len as u32),
-                                ),
-                                Some(DiagnosticSeverity::Warning),
-                                Some(NumberOrString::String(String::from(failed.name))),
-                                Some(String::from("svls")),
-                                String::from(failed.hint),
-                                None,
-                                None,
-                            ));
-                        }
-                    }
+            Ok((syntax_tree, _)) => {
+                let symbols = symbol::workspace_symbols(&syntax_tree, uri, s);
+                // Hold the index until the tree is cached, so indexing finished meanwhile finds
+                // the tree to lint the document again
+                let index = self.symbols.read().unwrap();
+                let duplicates =
+                    symbol::duplicate_diagnostics(&symbols, &index, workspace.locale.as_deref());
+                if !duplicates.is_empty() {
+                    let diags = ret.entry(uri.clone()).or_insert_with(Vec::new);
+                    diags.extend(duplicates);
+                    sort_diagnostics(diags);
+                }
+                let mut w = self.syntax_trees.write().unwrap();
+                // Keep the tree of a newer version parsed meanwhile
+                if w.get(uri).is_none_or(|(cached, _)| *cached <= version) {
+                    w.insert(uri.clone(), (version, Arc::new(syntax_tree)));
                 }
+                drop(w);
+                drop(index);
+                let mut w = self.last_diagnostics.write().unwrap();
+                w.insert(uri.clone(), ret[uri].clone());
             }
-            Err(x) => {
-                debug!("parse_error: {:?}", x);
-                match x {
-                    sv_parser::Error::Parse(Some((path, pos))) => {
-                        if path == PathBuf::from("") {
-                            let (line, col) = get_position(s, pos);
-                            let line_end = get_line_end(s, pos);
-                            let len = line_end - pos as u32;
-                            ret.push(Diagnostic::new(
-                                Range::new(
-                                    Position::new(line, col),
-                                    Position::new(line, col + len),
-                                ),
-                                Some(DiagnosticSeverity::Error),
-                                None,
-                                Some(String::from("svls")),
-                                String::from("parse error"),
-                                None,
-                                None,
-                            ));
-                        }
-                    }
-                    _ => (),
+            Err(_) => {
+                if let Some(last) = self.last_diagnostics.read().unwrap().get(uri) {
+                    let diags = ret.entry(uri.clone()).or_insert_with(Vec::new);
+                    diags.extend(last.iter().cloned());
+                    sort_diagnostics(diags);
                 }
             }
         }
+        // The last diagnostics are kept without the limit, so it is applied once to the merge
+        workspace.limit_diagnostics(&mut ret);
         ret
     }
-}
 
-#[async_trait]
-impl LanguageServer for Backend {
-    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
-        debug!("root_uri: {:?}", params.root_uri);
+    /// Get the syntax tree of the document `uri`, or the last parsed one if it fails to parse.
+    ///
+    /// The buffer being typed often fails to parse, but the stale tree is still useful for
+    /// completion and hover.
+    async fn last_syntax_tree(&self, uri: &Url) -> Option<Arc<SyntaxTree>> {
+        match self.syntax_tree(uri).await {
+            Some((_, x)) => Some(x),
+            None => self
+                .syntax_trees
+                .read()
+                .unwrap()
+                .get(uri)
+                .map(|(_, x)| x.clone()),
+        }
+    }
+
+    /// Get the text and the syntax tree of the document `uri`.
+    ///
+    /// The cached syntax tree is used if it has the current version of the document.
+    async fn syntax_tree(&self, uri: &Url) -> Option<(String, Arc<SyntaxTree>)> {
+        let (version, text) = {
+            let documents = self.documents.read().unwrap();
+            let document = documents.get(uri)?;
+            (document.version, document.text.clone())
+        };
+        if let Some((cached, syntax_tree)) = self.syntax_trees.read().unwrap().get(uri) {
+            if *cached == version {
+                return Some((text, syntax_tree.clone()));
+            }
+        }
+
+        let workspace = self.workspace(uri)?;
+        if workspace.size_diagnostic(&text).is_some() {
+            return None;
+        }
+        let parse_uri = uri.clone();
+        let (text, syntax_tree) = tokio::task::spawn_blocking(move || {
+            let (syntax_tree, _) = workspace.parse(&parse_uri, &text).ok()?;
+            Some((text, Arc::new(syntax_tree)))
+        })
+        .await
+        .ok()
+        .flatten()?;
+
+        let mut w = self.syntax_trees.write().unwrap();
+        w.insert(uri.clone(), (version, syntax_tree.clone()));
+        Some((text, syntax_tree))
+    }
+
+    /// Get the workspace of the nearest ancestor folder of `uri`.
+    ///
+    /// If no folder contains `uri`, the workspace of the root is used.
+    fn workspace(&self, uri: &Url) -> Option<Arc<Workspace>> {
+        let path = to_file_path(uri);
+        let nearest = self
+            .workspaces
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|x| {
+                let root = to_file_path(x.root_uri.as_ref()?)?;
+                if path.as_ref()?.starts_with(&root) {
+                    Some((root.components().count(), x))
+                } else {
+                    None
+                }
+            })
+            .max_by_key(|(depth, _)| *depth)
+            .map(|(_, x)| x.clone());
+        nearest.or_else(|| self.root_workspace())
+    }
+
+    /// Get the workspace of the root, or the first folder if the root is not a folder.
+    fn root_workspace(&self) -> Option<Arc<Workspace>> {
+        let workspaces = self.workspaces.read().unwrap();
+        let root_uri = self.root_uri.read().unwrap();
+        workspaces
+            .iter()
+            .find(|x| x.root_uri == *root_uri)
+            .or_else(|| workspaces.first())
+            .cloned()
+    }
+
+    /// Get the status of the server and the configuration of the root workspace.
+    fn status(&self) -> serde_json::Value {
+        let workspace = self.root_workspace();
+        let config = workspace.as_ref().and_then(|x| self.config_svls(x));
+        let svlint_config = workspace.as_ref().and_then(|x| self.config_svlint(x));
+        let linter = workspace
+            .as_ref()
+            .is_some_and(|x| x.config.read().unwrap().option.linter);
+        serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "config": config,
+            "svlint_config": svlint_config,
+            "linter": linter,
+            "documents": self.documents.read().unwrap().len(),
+            "locale": *self.locale.read().unwrap(),
+        })
+    }
+
+    /// Get the configuration in effect for `uri`, or the root workspace without `uri`.
+    ///
+    /// `config` is merged from `.svls.toml`, the client settings and the command line, with the
+    /// include paths resolved. `svlint` is the lint config after the rule overrides, or `null` if
+    /// the linter is disabled.
+    fn dump_config(&self, uri: Option<&Url>) -> serde_json::Value {
+        let workspace = match uri {
+            Some(uri) => self.workspace(uri),
+            None => self.root_workspace(),
+        };
+        let workspace = match workspace {
+            Some(x) => x,
+            None => return serde_json::Value::Null,
+        };
+        let mut config = workspace.config.read().unwrap().clone();
+        config.verilog.include_paths = workspace.include_paths();
+        let svlint = if config.option.linter {
+            let lint_config = effective_lint_config(self.config_svlint(&workspace), &config.option);
+            serde_json::to_value(lint_config).ok()
+        } else {
+            None
+        };
+        let ret = serde_json::json!({
+            "root": workspace.root_uri,
+            "config": config,
+            "svlint": svlint,
+        });
+        debug!(
+            "dump_config: {}",
+            serde_json::to_string_pretty(&ret).unwrap_or_default()
+        );
+        ret
+    }
+
+    async fn load_workspace(&self, root_uri: Option<Url>) -> Workspace {
+        let workspace = self.load_workspace_config(root_uri).await;
+        self.load_workspace_linter(&workspace).await;
+        workspace
+    }
+
+    /// Load `.svls.toml` of the workspace at `root_uri` without the linter.
+    async fn load_workspace_config(&self, root_uri: Option<Url>) -> Workspace {
+        debug!("workspace: {:?}", root_uri);
+        let mut workspace = Workspace {
+            root_uri,
+            config: RwLock::new(Config::default()),
+            linter: RwLock::new(None),
+            unit_defines: Default::default(),
+            locale: self.locale.read().unwrap().clone(),
+        };
 
-        let config_svls = search_config(&PathBuf::from(".svls.toml"));
+        let config_svls = self.config_svls(&workspace);
         debug!("config_svls: {:?}", config_svls);
         let config = match generate_config(config_svls) {
-            Ok(x) => x,
+            Ok((x, warnings)) => {
+                for warning in warnings {
+                    self.client
+                        .show_message(MessageType::Warning, warning)
+                        .await;
+                }
+                x
+            }
             Err(x) => {
-                self.client.show_message(MessageType::Warning, &x).await;
+                self.client
+                    .show_message(
+                        MessageType::Warning,
+                        format!("{} Enable all lint rules.", x),
+                    )
+                    .await;
                 Config::default()
             }
         };
+        let config = self.apply_settings(config).await;
+        let mut config = self.apply_command_file(&workspace, config).await;
+        config.append_verilog(&self.verilog);
 
-        if config.option.linter {
-            let config_svlint = search_config(&PathBuf::from(".svlint.toml"));
-            debug!("config_svlint: {:?}", config_svlint);
+        workspace.config = RwLock::new(config);
+        self.check_include_paths(&workspace).await;
+        workspace
+    }
 
-            let linter = match generate_linter(config_svlint) {
-                Ok(x) => x,
+    /// Load `.svlint.toml` of `workspace` if the linter is enabled.
+    async fn load_workspace_linter(&self, workspace: &Workspace) {
+        let option = workspace.config.read().unwrap().option.clone();
+        if option.linter {
+            let linter = self.load_linter(workspace, &option).await;
+            let mut w = workspace.linter.write().unwrap();
+            *w = Some(linter);
+        }
+    }
+
+    /// Warn about include paths of `workspace` which don't exist.
+    ///
+    /// Each path is reported only once.
+    async fn check_include_paths(&self, workspace: &Workspace) {
+        let missing: Vec<_> = {
+            let mut reported = self.missing_include_paths.write().unwrap();
+            workspace
+                .include_paths()
+                .into_iter()
+                .filter(|x| !x.exists() && reported.insert(x.clone()))
+                .collect()
+        };
+        if missing.is_empty() {
+            return;
+        }
+        let paths: Vec<_> = missing.iter().map(|x| x.to_string_lossy()).collect();
+        self.client
+            .show_message(
+                MessageType::Warning,
+                format!("Include paths not found: {}.", paths.join(", ")),
+            )
+            .await;
+    }
+
+    async fn load_linter(&self, workspace: &Workspace, option: &ConfigOption) -> Linter {
+        let config_svlint = self.config_svlint(workspace);
+        debug!("config_svlint: {:?}", config_svlint);
+
+        let (linter, warnings) = match generate_linter(config_svlint, option) {
+            Ok(x) => x,
+            Err(x) => default_linter(&x, option),
+        };
+        for warning in warnings {
+            self.client
+                .show_message(MessageType::Warning, warning)
+                .await;
+        }
+        linter
+    }
+
+    /// Reload `.svlint.toml` of all workspaces.
+    ///
+    /// Returns `false` if any of them fails to load, which keeps its current lint rules.
+    async fn reload_linter(&self) -> bool {
+        let mut ret = true;
+        let workspaces = self.workspaces.read().unwrap().clone();
+        for workspace in workspaces {
+            let option = workspace.config.read().unwrap().option.clone();
+            if !option.linter {
+                continue;
+            }
+
+            let config_svlint = self.config_svlint(&workspace);
+            debug!("config_svlint: {:?}", config_svlint);
+            let linter = match generate_linter(config_svlint, &option) {
+                Ok((x, warnings)) => {
+                    for warning in warnings {
+                        self.client
+                            .show_message(MessageType::Warning, warning)
+                            .await;
+                    }
+                    x
+                }
                 Err(x) => {
-                    self.client.show_message(MessageType::Warning, &x).await;
-                    Linter::new(LintConfig::new().enable_all())
+                    self.client
+                        .show_message(
+                            MessageType::Warning,
+                            format!("{} Keep the current lint rules.", x),
+                        )
+                        .await;
+                    ret = false;
+                    continue;
                 }
             };
 
-            let mut w = self.linter.write().unwrap();
+            let mut w = workspace.linter.write().unwrap();
             *w = Some(linter);
         }
 
-        let mut w = self.root_uri.write().unwrap();
-        *w = params.root_uri.clone();
+        self.lint_all();
+        ret
+    }
+
+    /// Reload `.svls.toml` of all workspaces.
+    ///
+    /// Returns `false` if any of them fails to load, which keeps its current configuration.
+    async fn reload_config(&self) -> bool {
+        let mut ret = true;
+        let workspaces = self.workspaces.read().unwrap().clone();
+        for workspace in workspaces {
+            let config_svls = self.config_svls(&workspace);
+            debug!("config_svls: {:?}", config_svls);
+            let config = match generate_config(config_svls) {
+                Ok((x, warnings)) => {
+                    for warning in warnings {
+                        self.client
+                            .show_message(MessageType::Warning, warning)
+                            .await;
+                    }
+                    x
+                }
+                Err(x) => {
+                    self.client
+                        .show_message(
+                            MessageType::Warning,
+                            format!("{} Keep the current configuration.", x),
+                        )
+                        .await;
+                    ret = false;
+                    continue;
+                }
+            };
+            let config = self.apply_settings(config).await;
+            let mut config = self.apply_command_file(&workspace, config).await;
+            config.append_verilog(&self.verilog);
 
-        let mut w = self.config.write().unwrap();
-        *w = Some(config);
+            let option = config.option.clone();
+            let rules_changed = {
+                let mut w = workspace.config.write().unwrap();
+                let prev = std::mem::replace(&mut *w, config);
+                workspace.clear_unit_defines();
+                prev.option.enable_rules != option.enable_rules
+                    || prev.option.disable_rules != option.disable_rules
+                    || prev.option.default_rules != option.default_rules
+            };
 
-        Ok(InitializeResult {
-            capabilities: ServerCapabilities {
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::Full,
-                )),
-                workspace: Some(WorkspaceServerCapabilities {
-                    workspace_folders: Some(WorkspaceFoldersServerCapabilities {
-                        supported: Some(true),
-                        change_notifications: Some(
-                            OneOf::Left(true),
-                        ),
-                    }),
-                    file_operations: None,
-                }),
-                ..ServerCapabilities::default()
-            },
-            server_info: Some(ServerInfo {
-                name: String::from("svls"),
-                version: Some(String::from(env!("CARGO_PKG_VERSION"))),
-            }),
-        })
-    }
+            self.check_include_paths(&workspace).await;
 
-    async fn initialized(&self, _: InitializedParams) {
-        self.client
-            .log_message(MessageType::Info, &format!("server initialized"))
-            .await;
-    }
+            let linter_loaded = workspace.linter.read().unwrap().is_some();
+            if !option.linter {
+                let mut w = workspace.linter.write().unwrap();
+                *w = None;
+            } else if !linter_loaded || rules_changed {
+                let linter = self.load_linter(&workspace, &option).await;
+                let mut w = workspace.linter.write().unwrap();
+                *w = Some(linter);
+            }
+        }
 
-    async fn shutdown(&self) -> Result<()> {
-        Ok(())
+        self.lint_all();
+        ret
     }
 
-    async fn did_change_workspace_folders(&self, _: DidChangeWorkspaceFoldersParams) {}
+    /// Merge `option.command_file` into `config`, showing a warning if it can't be read.
+    async fn apply_command_file(&self, workspace: &Workspace, mut config: Config) -> Config {
+        let root = workspace.root_uri.as_ref().and_then(to_file_path);
+        if let Err(x) = config.apply_command_file(root.as_deref()) {
+            self.client.show_message(MessageType::Warning, x).await;
+        }
+        config
+    }
 
-    async fn did_open(&self, params: DidOpenTextDocumentParams) {
-        debug!("did_open");
-        let diag = self.lint(&params.text_document.text);
-        self.client
-            .publish_diagnostics(
-                params.text_document.uri,
-                diag,
-                Some(params.text_document.version),
-            )
-            .await;
+    async fn apply_settings(&self, config: Config) -> Config {
+        let settings = self.settings.read().unwrap().clone();
+        if let Some(settings) = settings {
+            match config.merge(&settings) {
+                Ok(x) => return x,
+                Err(x) => {
+                    self.client
+                        .show_message(
+                            MessageType::Warning,
+                            format!("Failed to apply svls settings: {}", x),
+                        )
+                        .await;
+                }
+            }
+        }
+        config
     }
 
-    async fn did_change(&self, params: DidChangeTextDocumentParams) {
-        debug!("did_change");
-        let diag = self.lint(&params.content_changes[0].text);
-        self.client
-            .publish_diagnostics(params.text_document.uri, diag, Some(params.text_document.version))
-            .await;
+    /// Find SystemVerilog files under the workspace folders.
+    fn workspace_files(&self) -> Vec<PathBuf> {
+        let roots: Vec<_> = self
+            .workspaces
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|x| to_file_path(x.root_uri.as_ref()?))
+            .collect();
+        let mut files = Vec::new();
+        for root in roots {
+            find_files(&root, &["sv", "svh"], &mut files);
+        }
+        files.sort();
+        files.dedup();
+        files
     }
-}
 
-fn search_config(config: &Path) -> Option<PathBuf> {
-    if let Ok(current) = env::current_dir() {
-        for dir in current.ancestors() {
-            let candidate = dir.join(config);
-            if candidate.exists() {
-                return Some(candidate);
+    /// Index top-level declarations of SystemVerilog files under the workspace folders.
+    ///
+    /// Files indexed meanwhile by saving them are kept.
+    async fn index_workspace(&self) {
+        let files = self.workspace_files();
+        debug!("index_workspace: {} files", files.len());
+        for path in files {
+            let uri = match Url::from_file_path(&path) {
+                Ok(x) => x,
+                Err(_) => continue,
+            };
+            let workspace = match self.workspace(&uri) {
+                Some(x) => x,
+                None => continue,
+            };
+            let index_uri = uri.clone();
+            let symbols = tokio::task::spawn_blocking(move || {
+                let text = std::fs::read_to_string(&path).ok()?;
+                if workspace.size_diagnostic(&text).is_some() {
+                    return None;
+                }
+                let (syntax_tree, _) = workspace.parse(&index_uri, &text).ok()?;
+                Some(symbol::workspace_symbols(&syntax_tree, &index_uri, &text))
+            })
+            .await
+            .ok()
+            .flatten();
+            if let Some(symbols) = symbols {
+                let mut w = self.symbols.write().unwrap();
+                w.entry(uri).or_insert(symbols);
             }
         }
-        None
-    } else {
-        None
-    }
-}
 
-fn generate_config(config: Option<PathBuf>) -> std::result::Result<Config, String> {
-    if let Some(config) = config {
-        if let Ok(s) = std::fs::read_to_string(&config) {
-            if let Ok(config) = toml::from_str(&s) {
-                Ok(config)
-            } else {
-                Err(format!(
-                    "Failed to parse {}. Enable all lint rules.",
-                    config.to_string_lossy()
-                ))
+        // Documents linted before the index is built miss their duplicate declarations
+        let symbols = self.symbols.read().unwrap();
+        let documents = self.documents.read().unwrap();
+        let syntax_trees = self.syntax_trees.read().unwrap();
+        for (uri, document) in documents.iter() {
+            let duplicated = match syntax_trees.get(uri) {
+                Some((version, syntax_tree)) if *version == document.version => {
+                    let x = symbol::workspace_symbols(syntax_tree, uri, &document.text);
+                    !symbol::duplicate_diagnostics(&x, &symbols, None).is_empty()
+                }
+                _ => false,
+            };
+            if duplicated {
+                self.schedule_lint(
+                    uri.clone(),
+                    document.version,
+                    document.text.clone(),
+                    0,
+                    true,
+                );
             }
-        } else {
-            Err(format!(
-                "Failed to read {}. Enable all lint rules.",
-                config.to_string_lossy()
-            ))
         }
-    } else {
-        Ok(Config::default())
     }
-}
 
-fn generate_linter(config: Option<PathBuf>) -> std::result::Result<Linter, String> {
-    if let Some(config) = config {
-        if let Ok(s) = std::fs::read_to_string(&config) {
-            if let Ok(config) = toml::from_str(&s) {
-                Ok(Linter::new(config))
-            } else {
-                Err(format!(
-                    "Failed to parse {}. Enable all lint rules.",
-                    config.to_string_lossy()
-                ))
-            }
-        } else {
-            Err(format!(
-                "Failed to read {}. Enable all lint rules.",
-                config.to_string_lossy()
-            ))
+    /// Update the workspace symbol index by the document `uri`.
+    async fn index_document(&self, uri: &Url) {
+        if let Some((text, syntax_tree)) = self.syntax_tree(uri).await {
+            let symbols = symbol::workspace_symbols(&syntax_tree, uri, &text);
+            let mut w = self.symbols.write().unwrap();
+            w.insert(uri.clone(), symbols);
         }
-    } else {
-        Err(format!(".svlint.toml is not found. Enable all lint rules."))
     }
-}
 
-fn get_position(s: &str, pos: usize) -> (u32, u32) {
-    let mut line = 0;
-    let mut col = 0;
-    let mut p = 0;
-    while p < pos {
-        if let Some(c) = s.get(p..p + 1) {
-            if c == "\n" {
-                line += 1;
-                col = 0;
-            } else {
-                col += 1;
+    /// Lint all SystemVerilog files under the workspace folders, except open documents.
+    async fn lint_workspace(&self) -> usize {
+        let files = self.workspace_files();
+        let files: Vec<_> = {
+            let documents = self.documents.read().unwrap();
+            files
+                .into_iter()
+                .filter_map(|x| Some((Url::from_file_path(&x).ok()?, x)))
+                .filter(|(uri, _)| !documents.contains_key(uri))
+                .filter(|(uri, _)| {
+                    self.workspace(uri).is_some_and(|x| {
+                        x.config.read().unwrap().option.diagnostics && !x.is_excluded(uri)
+                    })
+                })
+                .collect()
+        };
+        debug!("lint_workspace: {} files", files.len());
+
+        let token = NumberOrString::String(String::from(LINT_WORKSPACE));
+        let progress = self.progress_begin(&token, "Linting workspace").await;
+        let total = files.len();
+        for (i, (uri, path)) in files.into_iter().enumerate() {
+            if progress {
+                let message = format!("{}/{} {}", i + 1, total, path.to_string_lossy());
+                let percentage = (i * 100 / total) as u32;
+                self.progress_report(&token, message, percentage).await;
+            }
+
+            let workspace = match self.workspace(&uri) {
+                Some(x) => x,
+                None => continue,
+            };
+            let lint_uri = uri.clone();
+            let diags = tokio::task::spawn_blocking(move || {
+                let text = std::fs::read_to_string(&path).ok()?;
+                if let Some(x) = workspace.size_diagnostic(&text) {
+                    return Some(vec![(lint_uri, vec![x])].into_iter().collect());
+                }
+                let (_, mut diags) = workspace.parse_and_lint(&lint_uri, &text, true);
+                workspace.limit_diagnostics(&mut diags);
+                Some(diags)
+            })
+            .await
+            .ok()
+            .flatten();
+            for (uri, diag) in diags.into_iter().flatten() {
+                self.publish(uri, None, diag).await;
             }
-        } else {
-            col += 1;
         }
-        p += 1;
+        if progress {
+            self.progress_end(&token, format!("Linted {} files", total))
+                .await;
+        }
+        total
     }
-    (line, col)
-}
 
-fn get_line_end(s: &str, pos: usize) -> u32 {
-    let mut p = pos;
-    while p < s.len() {
-        if let Some(c) = s.get(p..p + 1) {
-            if c == "\n" {
-                break;
-            }
+    /// Begin a work done progress if the client supports it.
+    ///
+    /// Returns whether the progress is created.
+    async fn progress_begin(&self, token: &ProgressToken, title: &str) -> bool {
+        let supported = {
+            let capabilities = self.capabilities.read().unwrap();
+            capabilities
+                .window
+                .as_ref()
+                .and_then(|x| x.work_done_progress)
+                .unwrap_or(false)
+        };
+        if !supported {
+            return false;
+        }
+        let params = WorkDoneProgressCreateParams {
+            token: token.clone(),
+        };
+        if let Err(x) = self
+            .client
+            .send_custom_request::<WorkDoneProgressCreate>(params)
+            .await
+        {
+            debug!("progress_error: {:?}", x);
+            return false;
         }
-        p += 1;
+        let begin = WorkDoneProgress::Begin(WorkDoneProgressBegin {
+            title: String::from(title),
+            cancellable: Some(false),
+            message: None,
+            percentage: Some(0),
+        });
+        self.progress(token, begin).await;
+        true
+    }
+
+    async fn progress_report(&self, token: &ProgressToken, message: String, percentage: u32) {
+        let report = WorkDoneProgress::Report(WorkDoneProgressReport {
+            cancellable: Some(false),
+            message: Some(message),
+            percentage: Some(percentage),
+        });
+        self.progress(token, report).await;
+    }
+
+    async fn progress_end(&self, token: &ProgressToken, message: String) {
+        let end = WorkDoneProgress::End(WorkDoneProgressEnd {
+            message: Some(message),
+        });
+        self.progress(token, end).await;
+    }
+
+    async fn progress(&self, token: &ProgressToken, value: WorkDoneProgress) {
+        let params = ProgressParams {
+            token: token.clone(),
+            value: ProgressParamsValue::WorkDone(value),
+        };
+        self.client
+            .send_custom_notification::<Progress>(params)
+            .await;
+    }
+
+    fn lint_all(&self) {
+        let documents = self.documents.read().unwrap();
+        for (uri, document) in documents.iter() {
+            self.schedule_lint(
+                uri.clone(),
+                document.version,
+                document.text.clone(),
+                0,
+                true,
+            );
+        }
+    }
+
+    /// Publish `diagnostics` of `uri`, computed from `version` of the document if it is open.
+    ///
+    /// Diagnostics of a version older than the last published one are discarded, so the version
+    /// seen by the client never goes back. Files which are not open are published without version.
+    async fn publish(&self, uri: Url, version: Option<i32>, diagnostics: Vec<Diagnostic>) {
+        if let Some(version) = version {
+            let mut w = self.published.write().unwrap();
+            if w.get(&uri).is_some_and(|x| *x > version) {
+                debug!("discard: {} {}", uri, version);
+                return;
+            }
+            w.insert(uri.clone(), version);
+        }
+        self.client
+            .publish_diagnostics(uri, diagnostics, version)
+            .await;
+    }
+
+    /// Lint `text` on the blocking thread pool after `delay_ms` and publish the result.
+    ///
+    /// A pending lint for the same URI is aborted, so only the latest version is published.
+    /// The diagnostics of `uri` are always published even if empty, which clears fixed failures.
+    /// Lint rules are checked only if `rules`.
+    fn schedule_lint(&self, uri: Url, version: i32, text: String, delay_ms: u64, rules: bool) {
+        let backend = self.clone();
+        let task_uri = uri.clone();
+        let task = tokio::spawn(async move {
+            if delay_ms != 0 {
+                tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+            }
+            // Bound the number of documents linted at once
+            let pool = backend.lint_pool.read().unwrap().clone();
+            let permit = match pool.acquire_owned().await {
+                Ok(x) => x,
+                Err(_) => return,
+            };
+            let linter = backend.clone();
+            let lint_uri = task_uri.clone();
+            let diags = match tokio::task::spawn_blocking(move || {
+                let _permit = permit;
+                linter.lint(&lint_uri, version, &text, rules)
+            })
+            .await
+            {
+                Ok(x) => x,
+                // Stale diagnostics are cleared as linting stopped halfway
+                Err(x) => {
+                    error!("lint_error: {:?}", x);
+                    backend.publish(task_uri, Some(version), Vec::new()).await;
+                    return;
+                }
+            };
+
+            // Clear diagnostics of included files which are not reported anymore
+            let stale: Vec<Url> = {
+                let mut w = backend.documents.write().unwrap();
+                match w.get_mut(&task_uri) {
+                    // The document has been changed while linting
+                    Some(document) if document.version > version => {
+                        debug!("discard: {} {}", task_uri, version);
+                        return;
+                    }
+                    Some(document) => {
+                        let included = diags.keys().filter(|x| **x != task_uri).cloned().collect();
+                        let prev = std::mem::replace(&mut document.included, included);
+                        prev.into_iter()
+                            .filter(|x| !diags.contains_key(x))
+                            .collect()
+                    }
+                    // The document is already closed
+                    None => return,
+                }
+            };
+            for uri in stale {
+                backend.publish(uri, None, Vec::new()).await;
+            }
+
+            for (uri, diag) in diags {
+                let version = if uri == task_uri { Some(version) } else { None };
+                backend.publish(uri, version, diag).await;
+            }
+        });
+        let mut w = self.pending.write().unwrap();
+        if let Some(prev) = w.insert(uri, task) {
+            prev.abort();
+        }
+    }
+}
+
+#[async_trait]
+impl LanguageServer for Backend {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        let root_uri = root_uri(&params);
+        debug!("root_uri: {:?}", root_uri);
+
+        {
+            let mut w = self.root_uri.write().unwrap();
+            *w = root_uri.clone();
+        }
+        {
+            let mut w = self.locale.write().unwrap();
+            *w = params.locale.clone();
+        }
+
+        let folders = match params.workspace_folders {
+            Some(ref x) if !x.is_empty() => x.iter().map(|x| Some(x.uri.clone())).collect(),
+            _ => vec![root_uri.clone()],
+        };
+        let mut workspaces = Vec::new();
+        for folder in folders {
+            workspaces.push(Arc::new(self.load_workspace_config(folder).await));
+        }
+
+        let root = workspaces
+            .iter()
+            .find(|x| x.root_uri == root_uri)
+            .or_else(|| workspaces.first());
+        if let Some(root) = root {
+            let path = root.root_uri.as_ref().and_then(to_file_path);
+            let result = init_logger(&root.config.read().unwrap(), path.as_deref());
+            if let Err(x) = result {
+                self.client.show_message(MessageType::Warning, x).await;
+            }
+        }
+        // Clients must accept both kinds, so only the root config decides
+        let text_sync = root
+            .map(|x| x.config.read().unwrap().option.text_sync)
+            .unwrap_or_default();
+        debug!("text_sync: {:?}", text_sync);
+        {
+            let mut w = self.text_sync.write().unwrap();
+            *w = text_sync;
+        }
+        let threads = lint_threads(root.and_then(|x| x.config.read().unwrap().option.lint_threads));
+        debug!("lint_threads: {}", threads);
+        {
+            let pool = Arc::new(Semaphore::new(threads));
+            // Documents opened before `initialized` are linted once the linters are loaded
+            let mut w = self.loading.write().unwrap();
+            *w = pool.clone().try_acquire_many_owned(threads as u32).ok();
+            let mut w = self.lint_pool.write().unwrap();
+            *w = pool;
+        }
+
+        let mut w = self.capabilities.write().unwrap();
+        *w = params.capabilities.clone();
+
+        let mut w = self.workspaces.write().unwrap();
+        *w = workspaces;
+
+        Ok(InitializeResult {
+            capabilities: ServerCapabilities {
+                text_document_sync: Some(TextDocumentSyncCapability::Options(
+                    TextDocumentSyncOptions {
+                        open_close: Some(true),
+                        change: Some(text_sync.into()),
+                        save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+                        ..Default::default()
+                    },
+                )),
+                workspace: Some(WorkspaceServerCapabilities {
+                    workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                        supported: Some(true),
+                        change_notifications: Some(OneOf::Left(true)),
+                    }),
+                    file_operations: None,
+                }),
+                definition_provider: Some(OneOf::Left(true)),
+                document_symbol_provider: Some(OneOf::Left(true)),
+                workspace_symbol_provider: Some(OneOf::Left(true)),
+                references_provider: Some(OneOf::Left(true)),
+                document_highlight_provider: Some(OneOf::Left(true)),
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: Default::default(),
+                })),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                semantic_tokens_provider: Some(
+                    SemanticTokensServerCapabilities::SemanticTokensOptions(
+                        SemanticTokensOptions {
+                            work_done_progress_options: Default::default(),
+                            legend: semantic::legend(),
+                            range: None,
+                            full: Some(SemanticTokensFullOptions::Bool(true)),
+                        },
+                    ),
+                ),
+                code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: vec![
+                        String::from(LINT_WORKSPACE),
+                        String::from(RELINT),
+                        String::from(RELOAD),
+                        String::from(STATUS),
+                        String::from(DUMP_CONFIG),
+                    ],
+                    work_done_progress_options: Default::default(),
+                }),
+                completion_provider: Some(CompletionOptions {
+                    trigger_characters: Some(vec![
+                        String::from("`"),
+                        String::from("."),
+                        String::from("\""),
+                        String::from("/"),
+                    ]),
+                    ..Default::default()
+                }),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+                document_link_provider: Some(DocumentLinkOptions {
+                    resolve_provider: Some(false),
+                    work_done_progress_options: Default::default(),
+                }),
+                ..ServerCapabilities::default()
+            },
+            server_info: Some(ServerInfo {
+                name: String::from("svls"),
+                version: Some(String::from(env!("CARGO_PKG_VERSION"))),
+            }),
+        })
+    }
+
+    async fn initialized(&self, _: InitializedParams) {
+        self.client
+            .log_message(MessageType::Info, "server initialized")
+            .await;
+
+        // Progress can't be reported until the server is initialized, so the linters are loaded here
+        let token = NumberOrString::String(String::from(LOAD_CONFIGURATION));
+        let progress = self
+            .progress_begin(&token, "Loading svls configuration")
+            .await;
+        let workspaces = self.workspaces.read().unwrap().clone();
+        for workspace in workspaces {
+            self.load_workspace_linter(&workspace).await;
+        }
+        if progress {
+            self.progress_end(&token, String::from("Loaded svls configuration"))
+                .await;
+        }
+        {
+            let mut w = self.loading.write().unwrap();
+            *w = None;
+        }
+        let backend = self.clone();
+        tokio::spawn(async move { backend.index_workspace().await });
+
+        let dynamic_registration = {
+            let capabilities = self.capabilities.read().unwrap();
+            capabilities
+                .workspace
+                .as_ref()
+                .and_then(|x| x.did_change_watched_files.as_ref())
+                .and_then(|x| x.dynamic_registration)
+                .unwrap_or(false)
+        };
+        if dynamic_registration {
+            let options = DidChangeWatchedFilesRegistrationOptions {
+                watchers: vec![
+                    FileSystemWatcher {
+                        glob_pattern: String::from("**/.svls.toml"),
+                        kind: None,
+                    },
+                    FileSystemWatcher {
+                        glob_pattern: String::from("**/.svlint.toml"),
+                        kind: None,
+                    },
+                ],
+            };
+            let registration = Registration {
+                id: String::from("svls-watched-files"),
+                method: String::from("workspace/didChangeWatchedFiles"),
+                register_options: serde_json::to_value(options).ok(),
+            };
+            if let Err(x) = self.client.register_capability(vec![registration]).await {
+                debug!("register_capability_error: {:?}", x);
+            }
+        }
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
+        debug!("did_change_workspace_folders");
+        let removed = params.event.removed;
+        let mut added = Vec::new();
+        for folder in params.event.added {
+            added.push(Arc::new(self.load_workspace(Some(folder.uri)).await));
+        }
+        {
+            let mut w = self.workspaces.write().unwrap();
+            w.retain(|x| !removed.iter().any(|y| x.root_uri.as_ref() == Some(&y.uri)));
+            w.extend(added);
+        }
+        self.lint_all();
+    }
+
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        debug!("did_change_configuration");
+        {
+            let mut w = self.settings.write().unwrap();
+            *w = params.settings.get("svls").cloned();
+        }
+        self.reload_config().await;
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        debug!("did_change_watched_files");
+        if params
+            .changes
+            .iter()
+            .any(|x| is_file_name(&x.uri, ".svls.toml"))
+        {
+            self.reload_config().await;
+        }
+        if params
+            .changes
+            .iter()
+            .any(|x| is_file_name(&x.uri, ".svlint.toml"))
+        {
+            self.reload_linter().await;
+        }
+    }
+
+    async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        debug!("did_open");
+        let text = params.text_document.text;
+        {
+            let mut w = self.documents.write().unwrap();
+            w.insert(
+                params.text_document.uri.clone(),
+                Document {
+                    version: params.text_document.version,
+                    text: text.clone(),
+                    included: Vec::new(),
+                },
+            );
+        }
+        self.schedule_lint(
+            params.text_document.uri,
+            params.text_document.version,
+            text,
+            0,
+            true,
+        );
+    }
+
+    async fn did_change(&self, params: DidChangeTextDocumentParams) {
+        debug!("did_change");
+        let text = {
+            let mut w = self.documents.write().unwrap();
+            let document = w.entry(params.text_document.uri.clone()).or_default();
+            document.version = params.text_document.version;
+            let text_sync = *self.text_sync.read().unwrap();
+            if text_sync == TextSync::Full {
+                // Ranges are ignored because they are not requested
+                if let Some(change) = params.content_changes.last() {
+                    document.text = change.text.clone();
+                }
+            } else {
+                for change in &params.content_changes {
+                    apply_change(&mut document.text, change);
+                }
+            }
+            document.text.clone()
+        };
+
+        let (lint_on, mode, debounce_ms) = match self.workspace(&params.text_document.uri) {
+            Some(workspace) => {
+                let config = workspace.config.read().unwrap();
+                (
+                    config.option.lint_on,
+                    config.option.mode,
+                    config.option.debounce_ms,
+                )
+            }
+            None => (LintOn::Change, Mode::Lint, 0),
+        };
+        if lint_on == LintOn::Save {
+            return;
+        }
+        self.schedule_lint(
+            params.text_document.uri,
+            params.text_document.version,
+            text,
+            debounce_ms,
+            mode == Mode::Lint,
+        );
+    }
+
+    async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        debug!("did_save");
+        let uri = params.text_document.uri;
+        // Macros defined by the saved file may be visible from the following files
+        for workspace in self.workspaces.read().unwrap().iter() {
+            workspace.clear_unit_defines();
+        }
+        self.index_document(&uri).await;
+        let (lint_on, mode) = match self.workspace(&uri) {
+            Some(workspace) => {
+                let config = workspace.config.read().unwrap();
+                (config.option.lint_on, config.option.mode)
+            }
+            None => (LintOn::Change, Mode::Lint),
+        };
+        // The lint rules are deferred to save in the syntax mode
+        if lint_on != LintOn::Save && mode != Mode::Syntax {
+            return;
+        }
+
+        let (version, text) = match self.documents.read().unwrap().get(&uri) {
+            Some(x) => (x.version, x.text.clone()),
+            None => return,
+        };
+        self.schedule_lint(uri, version, text, 0, true);
+    }
+
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        debug!("did_close");
+        let uri = params.text_document.uri;
+        if let Some(task) = self.pending.write().unwrap().remove(&uri) {
+            task.abort();
+        }
+        let document = self.documents.write().unwrap().remove(&uri);
+        self.syntax_trees.write().unwrap().remove(&uri);
+        self.last_diagnostics.write().unwrap().remove(&uri);
+
+        let included = document.map(|x| x.included).unwrap_or_default();
+        for uri in included {
+            self.publish(uri, None, Vec::new()).await;
+        }
+        // The document may be opened again from an older version
+        self.published.write().unwrap().remove(&uri);
+        self.publish(uri, None, Vec::new()).await;
+    }
+
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        debug!("goto_definition");
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        let (text, syntax_tree) = match self.syntax_tree(&uri).await {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let location = tokio::task::spawn_blocking(move || {
+            let pos = get_offset(&text, &position);
+            define::goto_macro_definition(&syntax_tree, &uri, &text, pos)
+                .or_else(|| symbol::goto_module_definition(&syntax_tree, &uri, &text, pos))
+        })
+        .await
+        .ok()
+        .flatten();
+        Ok(location.map(GotoDefinitionResponse::Scalar))
+    }
+
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        debug!("references");
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+        let include_declaration = params.context.include_declaration;
+        let (text, syntax_tree) = match self.syntax_tree(&uri).await {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let locations = tokio::task::spawn_blocking(move || {
+            let pos = get_offset(&text, &position);
+            reference::references(&syntax_tree, &uri, &text, pos, include_declaration)
+        })
+        .await
+        .ok();
+        Ok(locations)
+    }
+
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        debug!("prepare_rename");
+        let (text, syntax_tree) = match self.syntax_tree(&params.text_document.uri).await {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let pos = get_offset(&text, &params.position);
+        let range = reference::prepare_rename(&syntax_tree, &text, pos);
+        Ok(range.map(PrepareRenameResponse::Range))
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        debug!("rename");
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+        let (text, syntax_tree) = match self.syntax_tree(&uri).await {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let pos = get_offset(&text, &position);
+        reference::rename(&syntax_tree, &uri, &text, pos, &params.new_name)
+            .map_err(Error::invalid_params)
+    }
+
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> Result<Option<Vec<DocumentHighlight>>> {
+        debug!("document_highlight");
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        let (text, syntax_tree) = match self.syntax_tree(&uri).await {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let highlights = tokio::task::spawn_blocking(move || {
+            let pos = get_offset(&text, &position);
+            reference::document_highlights(&syntax_tree, &text, pos)
+        })
+        .await
+        .ok();
+        Ok(highlights)
+    }
+
+    async fn semantic_tokens_full(
+        &self,
+        params: SemanticTokensParams,
+    ) -> Result<Option<SemanticTokensResult>> {
+        debug!("semantic_tokens_full");
+        let (text, syntax_tree) = match self.syntax_tree(&params.text_document.uri).await {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let data =
+            tokio::task::spawn_blocking(move || semantic::semantic_tokens(&syntax_tree, &text))
+                .await
+                .ok();
+        Ok(data.map(|data| {
+            SemanticTokensResult::Tokens(SemanticTokens {
+                result_id: None,
+                data,
+            })
+        }))
+    }
+
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> Result<Option<DocumentSymbolResponse>> {
+        debug!("document_symbol");
+        let (text, syntax_tree) = match self.syntax_tree(&params.text_document.uri).await {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let symbols = symbol::document_symbols(&syntax_tree, &text);
+        Ok(Some(DocumentSymbolResponse::Nested(symbols)))
+    }
+
+    async fn symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> Result<Option<Vec<SymbolInformation>>> {
+        debug!("symbol");
+        // Open documents may be newer than the index
+        let uris: Vec<_> = self.documents.read().unwrap().keys().cloned().collect();
+        let mut open = HashMap::new();
+        for uri in uris {
+            if let Some((text, syntax_tree)) = self.syntax_tree(&uri).await {
+                let symbols = symbol::workspace_symbols(&syntax_tree, &uri, &text);
+                open.insert(uri, symbols);
+            }
+        }
+        let mut ret: Vec<_> = {
+            let symbols = self.symbols.read().unwrap();
+            symbols
+                .iter()
+                .filter(|(uri, _)| !open.contains_key(*uri))
+                .flat_map(|(_, x)| x.iter().cloned())
+                .chain(open.values().flatten().cloned())
+                .filter(|x| symbol::is_fuzzy_match(&params.query, &x.name))
+                .collect()
+        };
+        ret.sort_by(|x, y| x.name.cmp(&y.name));
+        Ok(Some(ret))
+    }
+
+    async fn document_link(&self, params: DocumentLinkParams) -> Result<Option<Vec<DocumentLink>>> {
+        debug!("document_link");
+        let uri = params.text_document.uri;
+        let text = match self.documents.read().unwrap().get(&uri) {
+            Some(x) => x.text.clone(),
+            None => return Ok(None),
+        };
+        let mut include_paths = Vec::new();
+        if let Some(dir) = to_file_path(&uri).as_deref().and_then(Path::parent) {
+            include_paths.push(dir.to_path_buf());
+        }
+        if let Some(workspace) = self.workspace(&uri) {
+            include_paths.append(&mut workspace.include_paths());
+        }
+        Ok(Some(link::document_links(&text, &include_paths)))
+    }
+
+    async fn execute_command(
+        &self,
+        params: ExecuteCommandParams,
+    ) -> Result<Option<serde_json::Value>> {
+        debug!("execute_command: {}", params.command);
+        match params.command.as_str() {
+            LINT_WORKSPACE => {
+                let files = self.lint_workspace().await;
+                Ok(Some(serde_json::json!({ "files": files })))
+            }
+            RELINT => {
+                self.lint_all();
+                let documents = self.documents.read().unwrap().len();
+                Ok(Some(serde_json::json!({ "documents": documents })))
+            }
+            RELOAD => {
+                // Reloading the config also loads the linter if it is newly enabled
+                let config = self.reload_config().await;
+                let linter = self.reload_linter().await;
+                let reloaded = config && linter;
+                if reloaded {
+                    self.client
+                        .show_message(MessageType::Info, "Reloaded the configuration.")
+                        .await;
+                }
+                Ok(Some(serde_json::json!({ "reloaded": reloaded })))
+            }
+            STATUS => Ok(Some(self.status())),
+            DUMP_CONFIG => {
+                let uri = params
+                    .arguments
+                    .first()
+                    .and_then(|x| serde_json::from_value::<Url>(x.clone()).ok());
+                Ok(Some(self.dump_config(uri.as_ref())))
+            }
+            x => Err(Error::invalid_params(format!("Unknown command: {}", x))),
+        }
+    }
+
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        debug!("hover");
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        let text = match self.documents.read().unwrap().get(&uri) {
+            Some(x) => x.text.clone(),
+            None => return Ok(None),
+        };
+        let defines = match self.workspace(&uri) {
+            Some(workspace) => workspace.config.read().unwrap().verilog.defines.clone(),
+            None => Vec::new(),
+        };
+        let syntax_tree = self.last_syntax_tree(&uri).await;
+        let pos = get_offset(&text, &position);
+        Ok(define::hover(syntax_tree.as_deref(), &text, pos, &defines))
+    }
+
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        debug!("completion");
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+        let text = match self.documents.read().unwrap().get(&uri) {
+            Some(x) => x.text.clone(),
+            None => return Ok(None),
+        };
+        let defines = match self.workspace(&uri) {
+            Some(workspace) => workspace.config.read().unwrap().verilog.defines.clone(),
+            None => Vec::new(),
+        };
+        let snippets = {
+            let capabilities = self.capabilities.read().unwrap();
+            capabilities
+                .text_document
+                .as_ref()
+                .and_then(|x| x.completion.as_ref())
+                .and_then(|x| x.completion_item.as_ref())
+                .and_then(|x| x.snippet_support)
+                .unwrap_or(false)
+        };
+        let pos = get_offset(&text, &position);
+        let (include_paths, extensions) = match self.workspace(&uri) {
+            Some(workspace) => {
+                let include_paths = workspace.document_include_paths(to_file_path(&uri).as_deref());
+                let mut extensions = workspace
+                    .config
+                    .read()
+                    .unwrap()
+                    .option
+                    .header_extensions
+                    .clone();
+                extensions.push(String::from("sv"));
+                (include_paths, extensions)
+            }
+            None => (Vec::new(), Vec::new()),
+        };
+        if let Some(items) =
+            completion::include_completions(&text, pos, &include_paths, &extensions)
+        {
+            return Ok(Some(CompletionResponse::Array(items)));
+        }
+        // Quotes and slashes trigger the completion only for `include
+        let trigger = params.context.and_then(|x| x.trigger_character);
+        if matches!(trigger.as_deref(), Some("\"") | Some("/")) {
+            return Ok(None);
+        }
+        let syntax_tree = self.last_syntax_tree(&uri).await;
+        let items = completion::completions(syntax_tree.as_deref(), &text, pos, &defines, snippets);
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    async fn selection_range(
+        &self,
+        params: SelectionRangeParams,
+    ) -> Result<Option<Vec<SelectionRange>>> {
+        debug!("selection_range");
+        let (text, syntax_tree) = match self.syntax_tree(&params.text_document.uri).await {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let positions = params.positions;
+        let ranges = tokio::task::spawn_blocking(move || {
+            positions
+                .iter()
+                .map(|x| selection::selection_range(&syntax_tree, &text, get_offset(&text, x)))
+                .collect()
+        })
+        .await
+        .ok();
+        Ok(ranges)
+    }
+
+    async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        debug!("folding_range");
+        let (text, syntax_tree) = match self.syntax_tree(&params.text_document.uri).await {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        Ok(Some(folding::folding_ranges(&syntax_tree, &text)))
+    }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        debug!("formatting");
+        let uri = params.text_document.uri;
+        let text = match self.documents.read().unwrap().get(&uri) {
+            Some(x) => x.text.clone(),
+            None => return Ok(None),
+        };
+        let (command, timeout, dir) = match self.workspace(&uri) {
+            Some(workspace) => {
+                let config = workspace.config.read().unwrap();
+                (
+                    config.option.formatter.clone(),
+                    Duration::from_millis(config.option.formatter_timeout_ms),
+                    workspace.root_uri.as_ref().and_then(to_file_path),
+                )
+            }
+            None => return Ok(None),
+        };
+        if command.is_empty() {
+            return Ok(None);
+        }
+        let input = text.clone();
+        let formatted = tokio::task::spawn_blocking(move || {
+            format::run_formatter(&command, &input, dir.as_deref(), timeout)
+        })
+        .await
+        .unwrap_or_else(|x| Err(x.to_string()));
+        match formatted {
+            Ok(x) => Ok(Some(vec![format::full_edit(&text, x)])),
+            Err(x) => {
+                // Leave the document unchanged
+                warn!("formatter_error: {}", x);
+                Ok(None)
+            }
+        }
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        debug!("code_action");
+        let uri = params.text_document.uri;
+        let text = match self.documents.read().unwrap().get(&uri) {
+            Some(x) => x.text.clone(),
+            None => return Ok(None),
+        };
+        let actions = params
+            .context
+            .diagnostics
+            .iter()
+            .filter_map(|x| suppress_action(&uri, &text, x))
+            .map(CodeActionOrCommand::CodeAction)
+            .collect();
+        Ok(Some(actions))
+    }
+}
+
+/// Make a quick fix which surrounds the lines of `diagnostic` by `// svlint off/on` comments.
+fn suppress_action(uri: &Url, s: &str, diagnostic: &Diagnostic) -> Option<CodeAction> {
+    if diagnostic.source.as_deref() != Some("svls") {
+        return None;
+    }
+    let rule = match diagnostic.code {
+        Some(NumberOrString::String(ref x)) => x,
+        _ => return None,
+    };
+
+    let newline = if s.contains("\r\n") { "\r\n" } else { "\n" };
+    let start = Position::new(diagnostic.range.start.line, 0);
+    let start_offset = get_offset(s, &start);
+    let indent: String = s[start_offset..]
+        .chars()
+        .take_while(|x| *x == ' ' || *x == '\t')
+        .collect();
+
+    let end = get_line_end(
+        s,
+        get_offset(s, &Position::new(diagnostic.range.end.line, 0)),
+    );
+    let (end, on) = if end < s.len() {
+        let (line, _) = get_position(s, end);
+        (
+            Position::new(line + 1, 0),
+            format!("{}// svlint on {}{}", indent, rule, newline),
+        )
+    } else {
+        let (line, col) = get_position(s, end);
+        (
+            Position::new(line, col),
+            format!("{}{}// svlint on {}", newline, indent, rule),
+        )
+    };
+
+    let edits = vec![
+        TextEdit::new(
+            Range::new(start, start),
+            format!("{}// svlint off {}{}", indent, rule, newline),
+        ),
+        TextEdit::new(Range::new(end, end), on),
+    ];
+    let mut changes = HashMap::new();
+    changes.insert(uri.clone(), edits);
+
+    Some(CodeAction {
+        title: format!("Suppress {} for this line", rule),
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: Some(vec![diagnostic.clone()]),
+        edit: Some(WorkspaceEdit::new(changes)),
+        ..Default::default()
+    })
+}
+
+fn search_config(origin: Option<&Path>, config: &Path) -> Option<PathBuf> {
+    let origin = match origin {
+        Some(x) => x.to_path_buf(),
+        None => env::current_dir().ok()?,
+    };
+    for dir in origin.ancestors() {
+        let candidate = dir.join(config);
+        if candidate.exists() {
+            return Some(candidate);
+        }
+    }
+    None
+}
+
+/// Initialize the logger by `option.log_level` and `option.log_file`.
+///
+/// Nothing is done if the logger is already initialized by the command line.
+/// A relative `option.log_file` is resolved against `root`.
+fn init_logger(config: &Config, root: Option<&Path>) -> std::result::Result<(), String> {
+    if log::max_level() != LevelFilter::Off {
+        return Ok(());
+    }
+    if config.option.log_level.is_none() && config.option.log_file.is_none() {
+        return Ok(());
+    }
+
+    let level = match config.option.log_level {
+        Some(ref x) => x
+            .parse::<LevelFilter>()
+            .map_err(|_| format!("Unknown log level `{}`.", x))?,
+        None => LevelFilter::Debug,
+    };
+    let path = match config.option.log_file {
+        Some(ref x) => x.clone(),
+        None => PathBuf::from("svls.log"),
+    };
+    let path = match root {
+        Some(root) if path.is_relative() => root.join(path),
+        _ => path,
+    };
+    let file = File::create(&path)
+        .map_err(|x| format!("Failed to create {}: {}.", path.to_string_lossy(), x))?;
+    let _ = WriteLogger::init(level, simplelog::Config::default(), file);
+    debug!("start");
+    Ok(())
+}
+
+/// Lint the file at `path` as a document of the workspace at `root`, without the language server.
+///
+/// `.svls.toml` and `.svlint.toml` are searched from `root` unless given, and `verilog` is
+/// appended to the config. Warnings about them are returned along with the diagnostics.
+pub fn lint_file(
+    path: &Path,
+    root: &Path,
+    config_svls: Option<PathBuf>,
+    config_svlint: Option<PathBuf>,
+    verilog: &ConfigVerilog,
+) -> std::result::Result<(Vec<Diagnostic>, Vec<String>), String> {
+    let s = std::fs::read_to_string(path)
+        .map_err(|_| format!("Failed to read {}.", path.to_string_lossy()))?;
+
+    let config_svls = config_svls.or_else(|| search_config(Some(root), Path::new(".svls.toml")));
+    let (mut config, mut warnings) = generate_config(config_svls)?;
+    if let Err(x) = config.apply_command_file(Some(root)) {
+        warnings.push(x);
+    }
+    config.append_verilog(verilog);
+    let config_svlint =
+        config_svlint.or_else(|| search_config(Some(root), Path::new(".svlint.toml")));
+    let (mut linter, mut lint_warnings) = match generate_linter(config_svlint, &config.option) {
+        Ok(x) => x,
+        Err(x) => default_linter(&x, &config.option),
+    };
+    warnings.append(&mut lint_warnings);
+
+    // Resolve include paths as the language server does
+    let workspace = Workspace {
+        root_uri: Url::from_directory_path(root).ok(),
+        config: RwLock::new(config.clone()),
+        linter: RwLock::new(None),
+        unit_defines: Default::default(),
+        locale: None,
+    };
+    config.verilog.include_paths = workspace.document_include_paths(Some(path));
+    let mut diags = crate::lint_source(&s, &config, &mut linter);
+
+    // Refer to the file instead of the URI given by `lint_source`
+    if let Ok(uri) = Url::from_file_path(path) {
+        for x in diags
+            .iter_mut()
+            .flat_map(|x| x.related_information.iter_mut().flatten())
+        {
+            x.location.uri = uri.clone();
+        }
+    }
+    Ok((diags, warnings))
+}
+
+/// Load `config`, returning warnings about unknown keys along with it.
+fn generate_config(config: Option<PathBuf>) -> std::result::Result<(Config, Vec<String>), String> {
+    if let Some(config) = config {
+        if let Ok(s) = std::fs::read_to_string(&config) {
+            let mut warnings = Vec::new();
+            let ret = serde_ignored::deserialize(&mut toml::Deserializer::new(&s), |key| {
+                warnings.push(format!(
+                    "Unknown key `{}` in {}.",
+                    key,
+                    config.to_string_lossy()
+                ))
+            });
+            match ret {
+                Ok(ret) => Ok((ret, warnings)),
+                Err(x) => Err(format!(
+                    "Failed to parse {}: {}.",
+                    config.to_string_lossy(),
+                    x
+                )),
+            }
+        } else {
+            Err(format!("Failed to read {}.", config.to_string_lossy()))
+        }
+    } else {
+        Ok((Config::default(), Vec::new()))
+    }
+}
+
+/// Generate the linter from `config`, and apply the rule overrides of `option` on top of it.
+///
+/// Without `config`, only `option.enable_rules` are enabled if any.
+/// Unknown rule names in the overrides are returned as warnings.
+fn generate_linter(
+    config: Option<PathBuf>,
+    option: &ConfigOption,
+) -> std::result::Result<(Linter, Vec<String>), String> {
+    let (config, warnings) = generate_lint_config(config, option)?;
+    Ok((Linter::new(config), warnings))
+}
+
+/// Generate the lint config which `generate_linter` creates the linter from.
+fn generate_lint_config(
+    config: Option<PathBuf>,
+    option: &ConfigOption,
+) -> std::result::Result<(LintConfig, Vec<String>), String> {
+    let lint_config = if let Some(config) = config {
+        if let Ok(s) = std::fs::read_to_string(&config) {
+            match toml::from_str(&s) {
+                Ok(x) => x,
+                Err(x) => {
+                    return Err(format!(
+                        "Failed to parse {}: {}.",
+                        config.to_string_lossy(),
+                        x
+                    ))
+                }
+            }
+        } else {
+            return Err(format!("Failed to read {}.", config.to_string_lossy()));
+        }
+    } else if !option.enable_rules.is_empty() {
+        LintConfig::new()
+    } else {
+        return Err(String::from(".svlint.toml is not found."));
+    };
+    Ok(apply_rules(lint_config, option))
+}
+
+/// Get the lint config of `option.default_rules` and the message describing it.
+///
+/// `"recommended"` enables the functional rules, which catch likely bugs rather than style.
+fn default_lint_config(option: &ConfigOption) -> (LintConfig, &'static str) {
+    match option.default_rules {
+        DefaultRules::All => (LintConfig::new().enable_all(), "Enable all lint rules."),
+        DefaultRules::None => (LintConfig::new(), "Disable all lint rules."),
+        DefaultRules::Recommended => {
+            let recommended = ConfigOption {
+                enable_rules: lint::FUNCTIONAL_RULES
+                    .iter()
+                    .map(|x| String::from(*x))
+                    .collect(),
+                ..Default::default()
+            };
+            let (config, _) = apply_rules(LintConfig::new(), &recommended);
+            (config, "Enable the recommended lint rules.")
+        }
+    }
+}
+
+/// Generate the linter used because `.svlint.toml` can't be used for `reason`.
+///
+/// The first warning tells the reason and the rules enabled instead.
+fn default_linter(reason: &str, option: &ConfigOption) -> (Linter, Vec<String>) {
+    let (config, message) = default_lint_config(option);
+    let (config, mut warnings) = apply_rules(config, option);
+    warnings.insert(0, format!("{} {}", reason, message));
+    (Linter::new(config), warnings)
+}
+
+/// Get the lint config of the linter loaded from `config` with `option`, falling back to the defaults.
+fn effective_lint_config(config: Option<PathBuf>, option: &ConfigOption) -> LintConfig {
+    match generate_lint_config(config, option) {
+        Ok((x, _)) => x,
+        Err(_) => apply_rules(default_lint_config(option).0, option).0,
+    }
+}
+
+/// Enable `option.enable_rules` and then disable `option.disable_rules` in `config`.
+fn apply_rules(config: LintConfig, option: &ConfigOption) -> (LintConfig, Vec<String>) {
+    let mut warnings = Vec::new();
+    if option.enable_rules.is_empty() && option.disable_rules.is_empty() {
+        return (config, warnings);
+    }
+    let mut value = match serde_json::to_value(&config) {
+        Ok(x) => x,
+        Err(_) => return (config, warnings),
+    };
+    if let Some(rules) = value.get_mut("rules").and_then(|x| x.as_object_mut()) {
+        let overrides = [
+            ("enable_rules", &option.enable_rules, true),
+            ("disable_rules", &option.disable_rules, false),
+        ];
+        for (key, names, enabled) in overrides {
+            for name in names {
+                match rules.get_mut(name) {
+                    Some(x) => *x = serde_json::Value::Bool(enabled),
+                    None => warnings.push(format!("Unknown rule `{}` in option.{}.", name, key)),
+                }
+            }
+        }
+    }
+    match serde_json::from_value(value) {
+        Ok(x) => (x, warnings),
+        Err(_) => (config, warnings),
+    }
+}
+
+/// Get the root of the client, or its first workspace folder if the root is not given.
+fn root_uri(params: &InitializeParams) -> Option<Url> {
+    params.root_uri.clone().or_else(|| {
+        let folders = params.workspace_folders.as_ref()?;
+        folders.first().map(|x| x.uri.clone())
+    })
+}
+
+/// Convert `uri` to a file path, or log why it can't be converted.
+fn to_file_path(uri: &Url) -> Option<PathBuf> {
+    match uri.to_file_path() {
+        Ok(x) => Some(x),
+        Err(_) if uri.scheme() == "file" => {
+            warn!("Failed to convert {} to a file path", uri);
+            None
+        }
+        Err(_) => {
+            debug!("not a file: {}", uri);
+            None
+        }
+    }
+}
+
+/// Collect files with `extensions` under `dir` recursively.
+///
+/// Hidden files and files ignored by `.gitignore` or `.ignore` are skipped.
+fn find_files(dir: &Path, extensions: &[&str], files: &mut Vec<PathBuf>) {
+    let walker = ignore::WalkBuilder::new(dir).require_git(false).build();
+    for entry in walker.flatten() {
+        let path = entry.path();
+        if entry.file_type().is_some_and(|x| x.is_file())
+            && path
+                .extension()
+                .is_some_and(|x| extensions.iter().any(|y| x == *y))
+        {
+            files.push(path.to_path_buf());
+        }
+    }
+}
+
+fn is_glob(path: &Path) -> bool {
+    path.to_string_lossy().contains(&['*', '?', '['][..])
+}
+
+fn is_file_name(uri: &Url, name: &str) -> bool {
+    uri.path_segments().and_then(|mut x| x.next_back()) == Some(name)
+}
+
+/// Get the number of documents linted at once, which defaults to the number of CPUs.
+fn lint_threads(option: Option<usize>) -> usize {
+    option
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |x| x.get()))
+        .max(1)
+}
+
+fn apply_change(s: &mut String, change: &TextDocumentContentChangeEvent) {
+    if let Some(range) = change.range {
+        let beg = get_offset(s, &range.start);
+        let end = get_offset(s, &range.end).max(beg);
+        s.replace_range(beg..end, &change.text);
+    } else {
+        *s = change.text.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::io::{DuplexStream, ReadHalf, WriteHalf};
+    use tower_lsp::{LspService, Server};
+
+    /// Client driving the server over an in-memory duplex.
+    struct TestClient {
+        read: BufReader<ReadHalf<DuplexStream>>,
+        write: WriteHalf<DuplexStream>,
+        id: u64,
+        /// The server, to call its methods directly.
+        backend: Backend,
+    }
+
+    impl TestClient {
+        fn new() -> Self {
+            let mut backend = None;
+            let (service, messages) = LspService::new(|client| {
+                let x = Backend::new(client, None, None, ConfigVerilog::default());
+                backend = Some(x.clone());
+                x
+            });
+            let (client, server) = tokio::io::duplex(1024 * 1024);
+            let (server_read, server_write) = tokio::io::split(server);
+            tokio::spawn(
+                Server::new(server_read, server_write)
+                    .interleave(messages)
+                    .serve(service),
+            );
+            let (read, write) = tokio::io::split(client);
+            TestClient {
+                read: BufReader::new(read),
+                write,
+                id: 0,
+                backend: backend.unwrap(),
+            }
+        }
+
+        /// Start a client and initialize the server with the workspace `root`.
+        async fn start(root: &Path) -> Self {
+            let mut client = TestClient::new();
+            let root_uri = Url::from_file_path(root).unwrap();
+            client
+                .request(
+                    "initialize",
+                    json!({"processId": null, "rootUri": root_uri, "capabilities": {}}),
+                )
+                .await;
+            client.notify("initialized", json!({})).await;
+            client
+        }
+
+        /// Open the document `uri` with `text` as its first version.
+        async fn open(&mut self, uri: &Url, text: &str) {
+            self.notify(
+                "textDocument/didOpen",
+                json!({"textDocument": {
+                    "uri": uri, "languageId": "systemverilog", "version": 1, "text": text,
+                }}),
+            )
+            .await;
+        }
+
+        async fn send(&mut self, message: Value) {
+            let body = message.to_string();
+            let header = format!("Content-Length: {}\r\n\r\n", body.len());
+            self.write.write_all(header.as_bytes()).await.unwrap();
+            self.write.write_all(body.as_bytes()).await.unwrap();
+        }
+
+        async fn recv(&mut self) -> Value {
+            let mut len = 0;
+            loop {
+                let mut line = String::new();
+                self.read.read_line(&mut line).await.unwrap();
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                if let Some(x) = line.strip_prefix("Content-Length: ") {
+                    len = x.parse().unwrap();
+                }
+            }
+            let mut body = vec![0; len];
+            self.read.read_exact(&mut body).await.unwrap();
+            serde_json::from_slice(&body).unwrap()
+        }
+
+        async fn request(&mut self, method: &str, params: Value) -> Value {
+            self.id += 1;
+            let id = self.id;
+            self.send(json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}))
+                .await;
+            loop {
+                let message = self.recv().await;
+                if message["id"] == id && message.get("method").is_none() {
+                    return message["result"].clone();
+                }
+            }
+        }
+
+        async fn notify(&mut self, method: &str, params: Value) {
+            self.send(json!({"jsonrpc": "2.0", "method": method, "params": params}))
+                .await;
+        }
+
+        /// Wait for the notification of `method`, skipping other messages.
+        async fn notification(&mut self, method: &str) -> Value {
+            let wait = async {
+                loop {
+                    let message = self.recv().await;
+                    if message["method"] == method {
+                        return message["params"].clone();
+                    }
+                }
+            };
+            tokio::time::timeout(Duration::from_secs(30), wait)
+                .await
+                .unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_publish_version() {
+        let root = std::env::temp_dir().join("svls_test_publish_version");
+        std::fs::create_dir_all(&root).unwrap();
+        let uri = Url::from_file_path(root.join("a.sv")).unwrap();
+
+        let mut client = TestClient::start(&root).await;
+        client.open(&uri, "").await;
+        let params = client.notification("textDocument/publishDiagnostics").await;
+        assert_eq!(params["version"], 1);
+
+        let backend = client.backend.clone();
+        backend.publish(uri.clone(), Some(3), Vec::new()).await;
+        let params = client.notification("textDocument/publishDiagnostics").await;
+        assert_eq!(params["version"], 3);
+        // The older version is discarded
+        backend.publish(uri.clone(), Some(2), Vec::new()).await;
+        backend.publish(uri.clone(), Some(4), Vec::new()).await;
+        let params = client.notification("textDocument/publishDiagnostics").await;
+        assert_eq!(params["version"], 4);
+
+        client.request("shutdown", Value::Null).await;
+    }
+
+    #[tokio::test]
+    async fn test_publish_diagnostics() {
+        let root = std::env::temp_dir().join("svls_test_publish_diagnostics");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join(".svlint.toml"), "[rules]\nlegacy_always = true\n").unwrap();
+        let root_uri = Url::from_file_path(&root).unwrap();
+        let uri = Url::from_file_path(root.join("a.sv")).unwrap();
+
+        let mut client = TestClient::new();
+        let result = client
+            .request(
+                "initialize",
+                json!({"processId": null, "rootUri": root_uri, "capabilities": {}}),
+            )
+            .await;
+        assert_eq!(result["capabilities"]["definitionProvider"], true);
+        client.notify("initialized", json!({})).await;
+
+        let text = "module A;\n  always @* begin\n  end\nendmodule\n";
+        client.open(&uri, text).await;
+        let params = client.notification("textDocument/publishDiagnostics").await;
+        assert_eq!(params["uri"], json!(uri));
+        assert_eq!(params["version"], 1);
+        let diags = params["diagnostics"].as_array().unwrap();
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0]["code"], "legacy_always");
+        assert_eq!(
+            diags[0]["range"],
+            json!({"start": {"line": 1, "character": 2}, "end": {"line": 1, "character": 8}})
+        );
+
+        client.request("shutdown", Value::Null).await;
+    }
+
+    #[tokio::test]
+    async fn test_publish_empty_diagnostics() {
+        let root = std::env::temp_dir().join("svls_test_publish_empty_diagnostics");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join(".svlint.toml"), "[rules]\nlegacy_always = true\n").unwrap();
+        let uri = Url::from_file_path(root.join("a.sv")).unwrap();
+
+        let mut client = TestClient::start(&root).await;
+
+        let text = "module A;\n  always @* begin\n  end\nendmodule\n";
+        client.open(&uri, text).await;
+        let params = client.notification("textDocument/publishDiagnostics").await;
+        assert_eq!(params["diagnostics"].as_array().unwrap().len(), 1);
+
+        // Fixing the failure publishes an empty list for the new version
+        client
+            .notify(
+                "textDocument/didChange",
+                json!({
+                    "textDocument": {"uri": uri, "version": 2},
+                    "contentChanges": [{
+                        "range": {"start": {"line": 1, "character": 2}, "end": {"line": 1, "character": 11}},
+                        "text": "always_comb",
+                    }],
+                }),
+            )
+            .await;
+        let params = client.notification("textDocument/publishDiagnostics").await;
+        assert_eq!(params["uri"], json!(uri));
+        assert_eq!(params["version"], 2);
+        assert_eq!(params["diagnostics"], json!([]));
+
+        client.request("shutdown", Value::Null).await;
+    }
+
+    #[tokio::test]
+    async fn test_loading_progress() {
+        let root = std::env::temp_dir().join("svls_test_loading_progress");
+        std::fs::create_dir_all(&root).unwrap();
+        let root_uri = Url::from_file_path(&root).unwrap();
+
+        let mut client = TestClient::new();
+        let capabilities = json!({"window": {"workDoneProgress": true}});
+        client
+            .request(
+                "initialize",
+                json!({"processId": null, "rootUri": root_uri, "capabilities": capabilities}),
+            )
+            .await;
+        client.notify("initialized", json!({})).await;
+
+        let request = loop {
+            let message = client.recv().await;
+            if message["method"] == "window/workDoneProgress/create" {
+                break message;
+            }
+        };
+        assert_eq!(request["params"]["token"], LOAD_CONFIGURATION);
+        client
+            .send(json!({"jsonrpc": "2.0", "id": request["id"], "result": null}))
+            .await;
+        let params = client.notification("$/progress").await;
+        assert_eq!(params["value"]["kind"], "begin");
+        assert_eq!(params["value"]["title"], "Loading svls configuration");
+        let params = client.notification("$/progress").await;
+        assert_eq!(params["value"]["kind"], "end");
+
+        client.request("shutdown", Value::Null).await;
+    }
+
+    #[tokio::test]
+    async fn test_workspace_symbols() {
+        let root = std::env::temp_dir().join("svls_test_workspace_symbols");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("a.sv"), "module Alpha;\nendmodule\n").unwrap();
+        let uri = Url::from_file_path(root.join("b.sv")).unwrap();
+
+        let mut client = TestClient::start(&root).await;
+        client.open(&uri, "package Beta;\nendpackage\n").await;
+
+        // The files on disk are indexed in the background
+        let mut names = Vec::new();
+        for _ in 0..100 {
+            let result = client
+                .request("workspace/symbol", json!({"query": ""}))
+                .await;
+            names = result
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|x| x["name"].clone())
+                .collect();
+            if names.len() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(names, vec!["Alpha", "Beta"]);
+        let result = client
+            .request("workspace/symbol", json!({"query": "bt"}))
+            .await;
+        assert_eq!(result[0]["name"], "Beta");
+        assert_eq!(result[0]["location"]["uri"], json!(uri));
+        assert_eq!(result.as_array().unwrap().len(), 1);
+
+        client.request("shutdown", Value::Null).await;
+    }
+
+    #[tokio::test]
+    async fn test_dump_config() {
+        let root = std::env::temp_dir().join("svls_test_dump_config");
+        std::fs::create_dir_all(root.join("inc")).unwrap();
+        std::fs::write(
+            root.join(".svls.toml"),
+            "[verilog]\ninclude_paths = [\"inc\"]\n[option]\ndisable_rules = [\"legacy_always\"]\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join(".svlint.toml"),
+            "[rules]\nlegacy_always = true\ncase_default = true\n",
+        )
+        .unwrap();
+        let root_uri = Url::from_file_path(&root).unwrap();
+
+        let mut client = TestClient::start(&root).await;
+
+        let result = client
+            .request("workspace/executeCommand", json!({"command": DUMP_CONFIG}))
+            .await;
+        assert_eq!(result["root"], json!(root_uri));
+        assert_eq!(
+            result["config"]["verilog"]["include_paths"],
+            json!([root.join("inc")])
+        );
+        assert_eq!(result["svlint"]["rules"]["case_default"], true);
+        assert_eq!(result["svlint"]["rules"]["legacy_always"], false);
+
+        client.request("shutdown", Value::Null).await;
+    }
+
+    #[tokio::test]
+    async fn test_reload_default_rules() {
+        let root = std::env::temp_dir().join("svls_test_reload_default_rules");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(
+            root.join(".svls.toml"),
+            "[option]\ndefault_rules = \"none\"\n",
+        )
+        .unwrap();
+        let uri = Url::from_file_path(root.join("a.sv")).unwrap();
+
+        let mut client = TestClient::start(&root).await;
+        let text = "module A;\n  always @* begin\n  end\nendmodule\n";
+        client.open(&uri, text).await;
+        let params = client.notification("textDocument/publishDiagnostics").await;
+        assert_eq!(params["diagnostics"], json!([]));
+
+        std::fs::write(
+            root.join(".svls.toml"),
+            "[option]\ndefault_rules = \"all\"\n",
+        )
+        .unwrap();
+        let config_uri = Url::from_file_path(root.join(".svls.toml")).unwrap();
+        client
+            .notify(
+                "workspace/didChangeWatchedFiles",
+                json!({"changes": [{"uri": config_uri, "type": 2}]}),
+            )
+            .await;
+        let params = client.notification("textDocument/publishDiagnostics").await;
+        let codes: Vec<_> = params["diagnostics"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|x| x["code"].clone())
+            .collect();
+        assert!(codes.contains(&json!("legacy_always")));
+
+        client.request("shutdown", Value::Null).await;
+    }
+
+    #[tokio::test]
+    async fn test_duplicates_limited() {
+        let root = std::env::temp_dir().join("svls_test_duplicates_limited");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(
+            root.join(".svls.toml"),
+            "[option]\nmax_diagnostics_per_file = 1\n",
+        )
+        .unwrap();
+        std::fs::write(root.join(".svlint.toml"), "[rules]\nlegacy_always = true\n").unwrap();
+        std::fs::write(root.join("b.sv"), "module A;\nendmodule\n").unwrap();
+        let uri = Url::from_file_path(root.join("a.sv")).unwrap();
+
+        let mut client = TestClient::start(&root).await;
+        let text = "module A;\n  always @* begin\n  end\nendmodule\n";
+        client.open(&uri, text).await;
+        // The duplicate is reported once the workspace is indexed
+        let mut diagnostics = Vec::new();
+        for _ in 0..5 {
+            let params = client.notification("textDocument/publishDiagnostics").await;
+            diagnostics = params["diagnostics"].as_array().unwrap().clone();
+            if diagnostics.iter().any(|x| x["code"].is_null()) {
+                break;
+            }
+        }
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[0]["message"],
+            "Module `A` is declared in multiple files"
+        );
+        assert_eq!(diagnostics[1]["message"], "1 more diagnostics suppressed");
+
+        client.request("shutdown", Value::Null).await;
+    }
+
+    #[tokio::test]
+    async fn test_parse_error_limited() {
+        let root = std::env::temp_dir().join("svls_test_parse_error_limited");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(
+            root.join(".svls.toml"),
+            "[option]\nmax_diagnostics_per_file = 1\ndebounce_ms = 0\n",
+        )
+        .unwrap();
+        std::fs::write(root.join(".svlint.toml"), "[rules]\nlegacy_always = true\n").unwrap();
+        let uri = Url::from_file_path(root.join("a.sv")).unwrap();
+
+        let mut client = TestClient::start(&root).await;
+        let text = "module A;\n  always @* begin\n  end\n  always @* begin\n  end\nendmodule\n";
+        client.open(&uri, text).await;
+        let params = client.notification("textDocument/publishDiagnostics").await;
+        assert_eq!(params["diagnostics"].as_array().unwrap().len(), 2);
+
+        // The kept diagnostics are merged with the parse error before the limit
+        client
+            .notify(
+                "textDocument/didChange",
+                json!({
+                    "textDocument": {"uri": uri, "version": 2},
+                    "contentChanges": [{"text": format!("{}module (", text)}],
+                }),
+            )
+            .await;
+        let params = client.notification("textDocument/publishDiagnostics").await;
+        let diagnostics = params["diagnostics"].as_array().unwrap();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0]["code"], "legacy_always");
+        assert_eq!(diagnostics[1]["message"], "2 more diagnostics suppressed");
+
+        client.request("shutdown", Value::Null).await;
+    }
+
+    #[tokio::test]
+    async fn test_status() {
+        let root = std::env::temp_dir().join("svls_test_status");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join(".svls.toml"), "").unwrap();
+        std::fs::write(root.join(".svlint.toml"), "").unwrap();
+        let uri = Url::from_file_path(root.join("a.sv")).unwrap();
+
+        let mut client = TestClient::start(&root).await;
+        client.open(&uri, "").await;
+
+        let result = client
+            .request("workspace/executeCommand", json!({"command": STATUS}))
+            .await;
+        assert_eq!(result["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(result["config"], json!(root.join(".svls.toml")));
+        assert_eq!(result["svlint_config"], json!(root.join(".svlint.toml")));
+        assert_eq!(result["linter"], true);
+        assert_eq!(result["documents"], 1);
+
+        client.request("shutdown", Value::Null).await;
+    }
+
+    #[tokio::test]
+    async fn test_relint() {
+        let root = std::env::temp_dir().join("svls_test_relint");
+        std::fs::create_dir_all(&root).unwrap();
+        let uri = Url::from_file_path(root.join("a.sv")).unwrap();
+
+        let mut client = TestClient::start(&root).await;
+        client.open(&uri, "module A;\nendmodule\n").await;
+        client.notification("textDocument/publishDiagnostics").await;
+
+        let result = client
+            .request("workspace/executeCommand", json!({"command": RELINT}))
+            .await;
+        assert_eq!(result["documents"], 1);
+        let params = client.notification("textDocument/publishDiagnostics").await;
+        assert_eq!(params["uri"], json!(uri));
+        assert_eq!(params["version"], 1);
+
+        client.request("shutdown", Value::Null).await;
+    }
+
+    #[tokio::test]
+    async fn test_diagnostics_disabled() {
+        let root = std::env::temp_dir().join("svls_test_diagnostics_disabled");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join(".svls.toml"), "[option]\ndiagnostics = false\n").unwrap();
+        std::fs::write(root.join(".svlint.toml"), "[rules]\nlegacy_always = true\n").unwrap();
+        let uri = Url::from_file_path(root.join("a.sv")).unwrap();
+
+        let mut client = TestClient::start(&root).await;
+
+        let text = "module A;\n  always @* begin\n  end\nendmodule\n";
+        client.open(&uri, text).await;
+        let params = client.notification("textDocument/publishDiagnostics").await;
+        assert_eq!(params["uri"], json!(uri));
+        assert_eq!(params["diagnostics"], json!([]));
+
+        // Other features still work
+        let result = client
+            .request(
+                "textDocument/documentSymbol",
+                json!({"textDocument": {"uri": uri}}),
+            )
+            .await;
+        assert_eq!(result[0]["name"], "A");
+
+        client.request("shutdown", Value::Null).await;
+    }
+
+    #[tokio::test]
+    async fn test_syntax_mode() {
+        let root = std::env::temp_dir().join("svls_test_syntax_mode");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(
+            root.join(".svls.toml"),
+            "[option]\nmode = \"syntax\"\ndebounce_ms = 0\n",
+        )
+        .unwrap();
+        std::fs::write(root.join(".svlint.toml"), "[rules]\nlegacy_always = true\n").unwrap();
+        let uri = Url::from_file_path(root.join("a.sv")).unwrap();
+
+        let mut client = TestClient::start(&root).await;
+
+        let text = "module A;\n  always @* begin\n  end\nendmodule\n";
+        client.open(&uri, text).await;
+        let params = client.notification("textDocument/publishDiagnostics").await;
+        assert_eq!(params["diagnostics"][0]["code"], "legacy_always");
+
+        // Changes report parse errors only
+        client
+            .notify(
+                "textDocument/didChange",
+                json!({
+                    "textDocument": {"uri": uri, "version": 2},
+                    "contentChanges": [{"text": text}],
+                }),
+            )
+            .await;
+        let params = client.notification("textDocument/publishDiagnostics").await;
+        assert_eq!(params["version"], 2);
+        assert_eq!(params["diagnostics"], json!([]));
+
+        client
+            .notify(
+                "textDocument/didSave",
+                json!({"textDocument": {"uri": uri}}),
+            )
+            .await;
+        let params = client.notification("textDocument/publishDiagnostics").await;
+        assert_eq!(params["diagnostics"][0]["code"], "legacy_always");
+
+        client.request("shutdown", Value::Null).await;
+    }
+
+    #[tokio::test]
+    async fn test_malformed_input() {
+        let root = std::env::temp_dir().join("svls_test_malformed_input");
+        std::fs::create_dir_all(&root).unwrap();
+        let uri = Url::from_file_path(root.join("a.sv")).unwrap();
+
+        let mut client = TestClient::start(&root).await;
+
+        let text = "`define A `A\n`A\nmodule \u{0}(\n`define B(\n`include \"";
+        client.open(&uri, text).await;
+        let params = client.notification("textDocument/publishDiagnostics").await;
+        assert_eq!(params["version"], 1);
+
+        // The server still answers requests and lints the next version
+        let result = client
+            .request(
+                "textDocument/documentSymbol",
+                json!({"textDocument": {"uri": uri}}),
+            )
+            .await;
+        assert!(result.is_null() || result.is_array());
+        client
+            .notify(
+                "textDocument/didChange",
+                json!({
+                    "textDocument": {"uri": uri, "version": 2},
+                    "contentChanges": [{"text": "module A;\nendmodule\n"}],
+                }),
+            )
+            .await;
+        let params = client.notification("textDocument/publishDiagnostics").await;
+        assert_eq!(params["version"], 2);
+
+        client.request("shutdown", Value::Null).await;
+    }
+
+    #[tokio::test]
+    async fn test_lint_panic() {
+        let root = std::env::temp_dir().join("svls_test_lint_panic");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join(".svls.toml"), "[option]\ndebounce_ms = 0\n").unwrap();
+        std::fs::write(root.join(".svlint.toml"), "[rules]\nlegacy_always = true\n").unwrap();
+        let uri = Url::from_file_path(root.join("a.sv")).unwrap();
+
+        let mut client = TestClient::start(&root).await;
+        let text = "module A;\n  always @* begin\n  end\nendmodule\n";
+        client.open(&uri, text).await;
+        let params = client.notification("textDocument/publishDiagnostics").await;
+        assert_eq!(params["diagnostics"][0]["code"], "legacy_always");
+
+        // A panic inside linting or outside of it clears the diagnostics
+        let markers = [lint::PANIC_MARKER, lint::TASK_PANIC_MARKER];
+        for (version, marker) in (2..).zip(markers) {
+            client
+                .notify(
+                    "textDocument/didChange",
+                    json!({
+                        "textDocument": {"uri": uri, "version": version},
+                        "contentChanges": [{"text": format!("{}// {}\n", text, marker)}],
+                    }),
+                )
+                .await;
+            let params = client.notification("textDocument/publishDiagnostics").await;
+            assert_eq!(params["version"], version);
+            assert_eq!(params["diagnostics"], json!([]));
+        }
+
+        // The server still answers requests and lints the next version
+        let result = client
+            .request(
+                "textDocument/documentSymbol",
+                json!({"textDocument": {"uri": uri}}),
+            )
+            .await;
+        assert_eq!(result[0]["name"], "A");
+        client
+            .notify(
+                "textDocument/didChange",
+                json!({
+                    "textDocument": {"uri": uri, "version": 4},
+                    "contentChanges": [{"text": text}],
+                }),
+            )
+            .await;
+        let params = client.notification("textDocument/publishDiagnostics").await;
+        assert_eq!(params["version"], 4);
+        assert_eq!(params["diagnostics"][0]["code"], "legacy_always");
+
+        client.request("shutdown", Value::Null).await;
+    }
+
+    #[test]
+    fn test_lint_threads() {
+        assert_eq!(lint_threads(Some(3)), 3);
+        assert_eq!(lint_threads(Some(0)), 1);
+        assert!(lint_threads(None) >= 1);
+    }
+
+    #[test]
+    fn test_apply_change() {
+        let change = |range: Option<Range>, text: &str| TextDocumentContentChangeEvent {
+            range,
+            range_length: None,
+            text: String::from(text),
+        };
+        let range = |l0, c0, l1, c1| Some(Range::new(Position::new(l0, c0), Position::new(l1, c1)));
+
+        let mut s = String::from("module A;\r\nendmodule\r\n");
+        apply_change(&mut s, &change(range(0, 7, 0, 8), "B"));
+        apply_change(&mut s, &change(range(0, 9, 1, 0), "\r\n  reg a;\r\n"));
+        assert_eq!(s, "module B;\r\n  reg a;\r\nendmodule\r\n");
+
+        apply_change(&mut s, &change(range(1, 100, 2, 0), ""));
+        assert_eq!(s, "module B;\r\n  reg a;endmodule\r\n");
+
+        apply_change(&mut s, &change(None, "module C;\nendmodule\n"));
+        assert_eq!(s, "module C;\nendmodule\n");
+    }
+
+    #[test]
+    fn test_generate_config_unknown_key() {
+        let path = std::env::temp_dir().join("svls_test_generate_config_unknown_key.toml");
+        std::fs::write(
+            &path,
+            "[verilog]\nincluded_paths = [\"a\"]\ndefines = [\"A\"]\n[foo]\nbar = 1\n",
+        )
+        .unwrap();
+        let (config, warnings) = generate_config(Some(path.clone())).unwrap();
+        assert_eq!(config.verilog.defines, vec![String::from("A")]);
+        let path = path.to_string_lossy();
+        assert_eq!(
+            warnings,
+            vec![
+                format!("Unknown key `verilog.included_paths` in {}.", path),
+                format!("Unknown key `foo` in {}.", path),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_generate_config_parse_error() {
+        let path = std::env::temp_dir().join("svls_test_generate_config_parse_error.toml");
+        std::fs::write(&path, "[verilog]\ndefines = ]\n").unwrap();
+        let err = generate_config(Some(path.clone())).unwrap_err();
+        assert!(err.starts_with(&format!("Failed to parse {}: ", path.to_string_lossy())));
+        assert!(err.contains("line 2"), "{}", err);
+
+        let err = match generate_linter(Some(path.clone()), &ConfigOption::default()) {
+            Ok(_) => panic!("{} is parsed", path.to_string_lossy()),
+            Err(x) => x,
+        };
+        assert!(err.starts_with(&format!("Failed to parse {}: ", path.to_string_lossy())));
+        assert!(err.contains("line 2"), "{}", err);
+    }
+
+    #[test]
+    fn test_apply_rules() {
+        let option = ConfigOption {
+            enable_rules: vec![String::from("legacy_always"), String::from("foo")],
+            disable_rules: vec![String::from("wire_reg")],
+            ..Default::default()
+        };
+        let (config, warnings) = apply_rules(LintConfig::new().enable_all(), &option);
+        assert!(config.rules.legacy_always);
+        assert!(!config.rules.wire_reg);
+        assert!(config.rules.default_nettype_none);
+        assert_eq!(
+            warnings,
+            vec![String::from("Unknown rule `foo` in option.enable_rules.")]
+        );
+
+        let (config, _) = apply_rules(LintConfig::new(), &option);
+        assert!(config.rules.legacy_always);
+        assert!(!config.rules.default_nettype_none);
+    }
+
+    #[test]
+    fn test_default_lint_config() {
+        let mut option = ConfigOption::default();
+        let (config, message) = default_lint_config(&option);
+        assert!(config.rules.wire_reg);
+        assert_eq!(message, "Enable all lint rules.");
+
+        option.default_rules = DefaultRules::None;
+        let (config, _) = default_lint_config(&option);
+        assert!(!config.rules.wire_reg && !config.rules.legacy_always);
+
+        option.default_rules = DefaultRules::Recommended;
+        let (config, _) = default_lint_config(&option);
+        assert!(!config.rules.wire_reg && config.rules.legacy_always);
+        let (_, warnings) = default_linter(".svlint.toml is not found.", &option);
+        assert_eq!(
+            warnings,
+            vec![String::from(
+                ".svlint.toml is not found. Enable the recommended lint rules."
+            )]
+        );
+    }
+
+    #[test]
+    fn test_lint_parse_error_range() {
+        let workspace = Workspace::with_config(Config::default());
+        let uri = Url::parse("file:///test.sv").unwrap();
+        let s = "module A;\n  assign a = b +* c + d + e + f + g + h + i + j + k + l + m + n;\nendmodule\n";
+        let diags = workspace.lint(&uri, s, &workspace.parse(&uri, s));
+        let diags = &diags[&uri];
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].message, "parse error");
+        assert_eq!(
+            diags[0].range,
+            Range::new(Position::new(1, 15), Position::new(1, 17))
+        );
+    }
+
+    #[test]
+    fn test_lint_parse_error_disabled() {
+        let mut config = Config::default();
+        config.option.report_parse_errors = false;
+        let workspace = Workspace::with_config(config);
+        let uri = Url::parse("file:///test.sv").unwrap();
+        let s = "module A;\n  assign a = b +* c;\nendmodule\n";
+        let diags = workspace.lint(&uri, s, &workspace.parse(&uri, s));
+        assert_eq!(diags[&uri], Vec::new());
+    }
+
+    #[test]
+    fn test_parse_include_from_document_dir() {
+        let dir = std::env::temp_dir().join("svls test parse include from document dir");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.svh"), "module A;\nendmodule\n").unwrap();
+        let workspace = Workspace::with_config(Config::default());
+        // The document is not saved yet
+        let uri = Url::from_file_path(dir.join("b.sv")).unwrap();
+        let s = "`include \"a.svh\"\nmodule B;\nendmodule\n";
+        assert!(workspace.parse(&uri, s).is_ok());
+        let uri = Url::parse("untitled:Untitled-1").unwrap();
+        assert!(workspace.parse(&uri, s).is_err());
+    }
+
+    #[test]
+    fn test_unit_defines() {
+        let root = std::env::temp_dir().join("svls_test_unit_defines");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("a.sv"), "`define WIDTH 8\n").unwrap();
+        std::fs::write(root.join("b.sv"), "`define DEPTH `WIDTH\n").unwrap();
+        let mut config = Config::default();
+        config.option.compilation_order = vec![
+            PathBuf::from("a.sv"),
+            PathBuf::from("b.sv"),
+            PathBuf::from("c.sv"),
+        ];
+        let workspace = Workspace {
+            root_uri: Some(Url::from_directory_path(&root).unwrap()),
+            ..Workspace::with_config(config)
+        };
+        let s = "module C;\n  logic [`DEPTH-1:0] a;\nendmodule\n";
+        let uri = Url::from_file_path(root.join("c.sv")).unwrap();
+        assert!(workspace.parse(&uri, s).is_ok());
+        let uri = Url::from_file_path(root.join("a.sv")).unwrap();
+        assert!(workspace.parse(&uri, s).is_err());
+        let uri = Url::from_file_path(root.join("d.sv")).unwrap();
+        assert!(workspace.parse(&uri, s).is_err());
+    }
+
+    #[test]
+    fn test_lint_parse_error_header() {
+        let workspace = Workspace::with_config(Config::default());
+        let s = "  assign a = b +* c;\nendmodule\n";
+        let uri = Url::parse("file:///test.svh").unwrap();
+        let diags = workspace.lint(&uri, s, &workspace.parse(&uri, s));
+        assert_eq!(diags[&uri], Vec::new());
+        let uri = Url::parse("file:///test.sv").unwrap();
+        let diags = workspace.lint(&uri, s, &workspace.parse(&uri, s));
+        assert_eq!(diags[&uri].len(), 1);
+    }
+
+    #[test]
+    fn test_root_uri() {
+        let params = |x: Value| serde_json::from_value::<InitializeParams>(x).unwrap();
+        let folders = json!([
+            {"uri": "file:///a", "name": "a"},
+            {"uri": "file:///b", "name": "b"},
+        ]);
+        let root = params(json!({
+            "processId": null, "rootUri": "file:///b", "workspaceFolders": folders,
+            "capabilities": {},
+        }));
+        assert_eq!(root_uri(&root), Some(Url::parse("file:///b").unwrap()));
+        let folder = params(json!({
+            "processId": null, "rootUri": null, "workspaceFolders": folders, "capabilities": {},
+        }));
+        assert_eq!(root_uri(&folder), Some(Url::parse("file:///a").unwrap()));
+        let none = params(json!({"processId": null, "rootUri": null, "capabilities": {}}));
+        assert_eq!(root_uri(&none), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_to_file_path() {
+        let uri = Url::parse("file:///home/my%20project/%C3%A9/a%25b.sv").unwrap();
+        assert_eq!(
+            to_file_path(&uri),
+            Some(PathBuf::from("/home/my project/é/a%b.sv"))
+        );
+        let uri = Url::from_file_path("/home/my project/é/a.sv").unwrap();
+        assert_eq!(uri.as_str(), "file:///home/my%20project/%C3%A9/a.sv");
+        assert_eq!(
+            to_file_path(&uri),
+            Some(PathBuf::from("/home/my project/é/a.sv"))
+        );
+        assert_eq!(to_file_path(&Url::parse("file://host/a.sv").unwrap()), None);
+        assert_eq!(
+            to_file_path(&Url::parse("untitled:Untitled-1").unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_find_files() {
+        let dir = std::env::temp_dir().join("svls_test_find_files");
+        let _ = std::fs::remove_dir_all(&dir);
+        for x in &["src", "gen", "vendor", ".hidden"] {
+            std::fs::create_dir_all(dir.join(x)).unwrap();
+        }
+        std::fs::write(dir.join(".gitignore"), "/gen/\n").unwrap();
+        std::fs::write(dir.join(".ignore"), "vendor\n").unwrap();
+        for x in &[
+            "src/a.sv",
+            "src/b.svh",
+            "src/c.v",
+            "gen/d.sv",
+            "vendor/e.sv",
+            ".hidden/f.sv",
+        ] {
+            std::fs::write(dir.join(x), "").unwrap();
+        }
+        let mut files = Vec::new();
+        find_files(&dir, &["sv", "svh"], &mut files);
+        files.sort();
+        assert_eq!(files, vec![dir.join("src/a.sv"), dir.join("src/b.svh")]);
+    }
+
+    #[test]
+    fn test_lint_file() {
+        let root = std::env::temp_dir().join("svls_test_lint_file");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(root.join("include")).unwrap();
+        std::fs::write(
+            root.join(".svls.toml"),
+            "[verilog]\ninclude_paths = [\"include\"]\n",
+        )
+        .unwrap();
+        std::fs::write(root.join(".svlint.toml"), "[rules]\nlegacy_always = true\n").unwrap();
+        std::fs::write(root.join("include/a.svh"), "`define A 1\n").unwrap();
+        let s = "`include \"a.svh\"\nmodule A;\n  always @* begin\n  end\nendmodule\n";
+        std::fs::write(root.join("src/a.sv"), s).unwrap();
+
+        let (diags, warnings) = lint_file(
+            &root.join("src/a.sv"),
+            &root,
+            None,
+            None,
+            &Default::default(),
+        )
+        .unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(diags.len(), 1);
+        assert_eq!(
+            diags[0].code,
+            Some(NumberOrString::String(String::from("legacy_always")))
+        );
+        assert!(lint_file(
+            &root.join("src/b.sv"),
+            &root,
+            None,
+            None,
+            &Default::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_is_excluded() {
+        let mut config = Config::default();
+        config.option.exclude = vec![String::from("gen/**/*.sv"), String::from("*_tb.sv")];
+        let workspace = Workspace {
+            root_uri: Some(Url::parse("file:///ws/").unwrap()),
+            ..Workspace::with_config(config)
+        };
+        let excluded = |x: &str| workspace.is_excluded(&Url::parse(x).unwrap());
+        assert!(excluded("file:///ws/gen/a/b.sv"));
+        assert!(excluded("file:///ws/top_tb.sv"));
+        assert!(!excluded("file:///ws/src/top.sv"));
+        assert!(!excluded("file:///other/gen/a/b.sv"));
+
+        {
+            let mut w = workspace.config.write().unwrap();
+            w.option.include_patterns = vec![String::from("src/**/*.sv")];
+        }
+        assert!(!excluded("file:///ws/src/top.sv"));
+        assert!(excluded("file:///ws/legacy/top.v"));
+        assert!(excluded("file:///ws/src/top_tb.sv"));
+    }
+
+    #[test]
+    fn test_size_diagnostic() {
+        let mut config = Config::default();
+        config.option.max_file_bytes = 16;
+        let workspace = Workspace::with_config(config);
+        assert!(workspace.size_diagnostic("module A;\nendmodule").is_some());
+        assert!(workspace.size_diagnostic("module A; end").is_none());
+        assert_eq!(Config::default().option.max_file_bytes, 2 * 1024 * 1024);
+    }
+
+    #[test]
+    fn test_suppress_action() {
+        let uri = Url::parse("file:///test.sv").unwrap();
+        let diagnostic = |l0, c0, l1, c1| {
+            Diagnostic::new(
+                Range::new(Position::new(l0, c0), Position::new(l1, c1)),
+                Some(DiagnosticSeverity::Warning),
+                Some(NumberOrString::String(String::from("legacy_always"))),
+                Some(String::from("svls")),
+                String::from("hint"),
+                None,
+                None,
+            )
+        };
+        let suppress = |s: &str, diagnostic: &Diagnostic| {
+            let action = suppress_action(&uri, s, diagnostic).unwrap();
+            assert_eq!(action.title, "Suppress legacy_always for this line");
+            let mut edits = action.edit.unwrap().changes.unwrap().remove(&uri).unwrap();
+            edits.reverse();
+            let mut s = String::from(s);
+            for edit in edits {
+                apply_change(
+                    &mut s,
+                    &TextDocumentContentChangeEvent {
+                        range: Some(edit.range),
+                        range_length: None,
+                        text: edit.new_text,
+                    },
+                );
+            }
+            s
+        };
+
+        let s = "module A;\n  always @* begin\n  end\nendmodule\n";
+        assert_eq!(
+            suppress(s, &diagnostic(1, 2, 2, 5)),
+            "module A;\n  // svlint off legacy_always\n  always @* begin\n  end\n  // svlint on legacy_always\nendmodule\n"
+        );
+
+        let s = "always @* begin end";
+        assert_eq!(
+            suppress(s, &diagnostic(0, 0, 0, 6)),
+            "// svlint off legacy_always\nalways @* begin end\n// svlint on legacy_always"
+        );
+
+        let s = "module A;\r\nalways @* begin end\r\nendmodule\r\n";
+        assert_eq!(
+            suppress(s, &diagnostic(1, 0, 1, 6)),
+            "module A;\r\n// svlint off legacy_always\r\nalways @* begin end\r\n// svlint on legacy_always\r\nendmodule\r\n"
+        );
+
+        let mut parse_error = diagnostic(0, 0, 0, 6);
+        parse_error.code = None;
+        assert!(suppress_action(&uri, s, &parse_error).is_none());
     }
-    p as u32
 }
@@ -0,0 +1,817 @@
+use crate::define::{get_config_macros, get_macros};
+use crate::symbol::{get_identifier, get_local_offset};
+use std::path::{Path, PathBuf};
+use sv_parser::{unwrap_node, RefNode, SyntaxTree};
+use tower_lsp::lsp_types::*;
+
+/// Keywords of IEEE 1800-2017.
+pub const KEYWORDS: &[&str] = &[
+    "accept_on",
+    "alias",
+    "always",
+    "always_comb",
+    "always_ff",
+    "always_latch",
+    "and",
+    "assert",
+    "assign",
+    "assume",
+    "automatic",
+    "before",
+    "begin",
+    "bind",
+    "bins",
+    "binsof",
+    "bit",
+    "break",
+    "buf",
+    "bufif0",
+    "bufif1",
+    "byte",
+    "case",
+    "casex",
+    "casez",
+    "cell",
+    "chandle",
+    "checker",
+    "class",
+    "clocking",
+    "cmos",
+    "config",
+    "const",
+    "constraint",
+    "context",
+    "continue",
+    "cover",
+    "covergroup",
+    "coverpoint",
+    "cross",
+    "deassign",
+    "default",
+    "defparam",
+    "design",
+    "disable",
+    "dist",
+    "do",
+    "edge",
+    "else",
+    "end",
+    "endcase",
+    "endchecker",
+    "endclass",
+    "endclocking",
+    "endconfig",
+    "endfunction",
+    "endgenerate",
+    "endgroup",
+    "endinterface",
+    "endmodule",
+    "endpackage",
+    "endprimitive",
+    "endprogram",
+    "endproperty",
+    "endspecify",
+    "endsequence",
+    "endtable",
+    "endtask",
+    "enum",
+    "event",
+    "eventually",
+    "expect",
+    "export",
+    "extends",
+    "extern",
+    "final",
+    "first_match",
+    "for",
+    "force",
+    "foreach",
+    "forever",
+    "fork",
+    "forkjoin",
+    "function",
+    "generate",
+    "genvar",
+    "global",
+    "highz0",
+    "highz1",
+    "if",
+    "iff",
+    "ifnone",
+    "ignore_bins",
+    "illegal_bins",
+    "implements",
+    "implies",
+    "import",
+    "incdir",
+    "include",
+    "initial",
+    "inout",
+    "input",
+    "inside",
+    "instance",
+    "int",
+    "integer",
+    "interconnect",
+    "interface",
+    "intersect",
+    "join",
+    "join_any",
+    "join_none",
+    "large",
+    "let",
+    "liblist",
+    "library",
+    "local",
+    "localparam",
+    "logic",
+    "longint",
+    "macromodule",
+    "matches",
+    "medium",
+    "modport",
+    "module",
+    "nand",
+    "negedge",
+    "nettype",
+    "new",
+    "nexttime",
+    "nmos",
+    "nor",
+    "noshowcancelled",
+    "not",
+    "notif0",
+    "notif1",
+    "null",
+    "or",
+    "output",
+    "package",
+    "packed",
+    "parameter",
+    "pmos",
+    "posedge",
+    "primitive",
+    "priority",
+    "program",
+    "property",
+    "protected",
+    "pull0",
+    "pull1",
+    "pulldown",
+    "pullup",
+    "pulsestyle_ondetect",
+    "pulsestyle_onevent",
+    "pure",
+    "rand",
+    "randc",
+    "randcase",
+    "randsequence",
+    "rcmos",
+    "real",
+    "realtime",
+    "ref",
+    "reg",
+    "reject_on",
+    "release",
+    "repeat",
+    "restrict",
+    "return",
+    "rnmos",
+    "rpmos",
+    "rtran",
+    "rtranif0",
+    "rtranif1",
+    "s_always",
+    "s_eventually",
+    "s_nexttime",
+    "s_until",
+    "s_until_with",
+    "scalared",
+    "sequence",
+    "shortint",
+    "shortreal",
+    "showcancelled",
+    "signed",
+    "small",
+    "soft",
+    "solve",
+    "specify",
+    "specparam",
+    "static",
+    "string",
+    "strong",
+    "strong0",
+    "strong1",
+    "struct",
+    "super",
+    "supply0",
+    "supply1",
+    "sync_accept_on",
+    "sync_reject_on",
+    "table",
+    "tagged",
+    "task",
+    "this",
+    "throughout",
+    "time",
+    "timeprecision",
+    "timeunit",
+    "tran",
+    "tranif0",
+    "tranif1",
+    "tri",
+    "tri0",
+    "tri1",
+    "triand",
+    "trior",
+    "trireg",
+    "type",
+    "typedef",
+    "union",
+    "unique",
+    "unique0",
+    "unsigned",
+    "until",
+    "until_with",
+    "untyped",
+    "use",
+    "uwire",
+    "var",
+    "vectored",
+    "virtual",
+    "void",
+    "wait",
+    "wait_order",
+    "wand",
+    "weak",
+    "weak0",
+    "weak1",
+    "while",
+    "wildcard",
+    "wire",
+    "with",
+    "within",
+    "wor",
+    "xnor",
+    "xor",
+];
+
+/// Compiler directives of IEEE 1800-2017 without the leading backtick.
+const DIRECTIVES: &[&str] = &[
+    "__FILE__",
+    "__LINE__",
+    "begin_keywords",
+    "celldefine",
+    "default_nettype",
+    "define",
+    "else",
+    "elsif",
+    "end_keywords",
+    "endcelldefine",
+    "endif",
+    "ifdef",
+    "ifndef",
+    "include",
+    "line",
+    "nounconnected_drive",
+    "pragma",
+    "resetall",
+    "timescale",
+    "unconnected_drive",
+    "undef",
+    "undefineall",
+];
+
+/// Snippets of common constructs with their labels and descriptions.
+const SNIPPETS: &[(&str, &str, &str)] = &[
+    (
+        "module",
+        "module ${1:name} (\n\t$2\n);\n\t$0\nendmodule",
+        "module declaration",
+    ),
+    (
+        "always_ff",
+        "always_ff @(posedge ${1:clk}) begin\n\t$0\nend",
+        "always_ff block",
+    ),
+    (
+        "always_comb",
+        "always_comb begin\n\t$0\nend",
+        "always_comb block",
+    ),
+    (
+        "case",
+        "case (${1:expr})\n\t${2:value}: $3\n\tdefault: $0\nendcase",
+        "case statement",
+    ),
+    (
+        "for",
+        "for (int ${1:i} = 0; $1 < ${2:N}; $1++) begin\n\t$0\nend",
+        "for loop",
+    ),
+];
+
+/// Get completion items at byte offset `pos` of `s`.
+///
+/// Compiler directives and macros are offered after a backtick, unconnected ports of the module
+/// after a `.` in the port connections of an instance, and keywords and identifiers declared in
+/// the current document otherwise.
+/// `defines` are the macros given by the configuration in the form of `NAME` or `NAME=VALUE`.
+/// Snippets of common constructs are added with keywords if `snippets` is true.
+pub fn completions(
+    syntax_tree: Option<&SyntaxTree>,
+    s: &str,
+    pos: usize,
+    defines: &[String],
+    snippets: bool,
+) -> Vec<CompletionItem> {
+    let mut ret = Vec::new();
+    if is_directive(s, pos) {
+        for x in DIRECTIVES {
+            ret.push(CompletionItem {
+                label: String::from(*x),
+                kind: Some(CompletionItemKind::Keyword),
+                detail: Some(String::from("compiler directive")),
+                ..Default::default()
+            });
+        }
+        let mut macros = get_config_macros(defines);
+        if let Some(syntax_tree) = syntax_tree {
+            macros.append(&mut get_macros(syntax_tree));
+        }
+        for x in macros {
+            if let Some(item) = ret.iter_mut().find(|item| item.label == x.name) {
+                // Definitions in the document override the configuration
+                if item.kind == Some(CompletionItemKind::Constant) {
+                    item.detail = x.text;
+                }
+                continue;
+            }
+            ret.push(CompletionItem {
+                label: x.name,
+                kind: Some(CompletionItemKind::Constant),
+                detail: x.text,
+                ..Default::default()
+            });
+        }
+        return ret;
+    }
+
+    if let Some(syntax_tree) = syntax_tree {
+        if let Some(items) = port_completions(syntax_tree, s, pos) {
+            return items;
+        }
+    }
+
+    for x in KEYWORDS {
+        ret.push(CompletionItem {
+            label: String::from(*x),
+            kind: Some(CompletionItemKind::Keyword),
+            ..Default::default()
+        });
+    }
+    if snippets {
+        for (label, body, detail) in SNIPPETS {
+            ret.push(CompletionItem {
+                label: String::from(*label),
+                kind: Some(CompletionItemKind::Snippet),
+                detail: Some(String::from(*detail)),
+                insert_text: Some(String::from(*body)),
+                insert_text_format: Some(InsertTextFormat::Snippet),
+                ..Default::default()
+            });
+        }
+    }
+    if let Some(syntax_tree) = syntax_tree {
+        for (name, kind) in get_declared_identifiers(syntax_tree) {
+            if ret.iter().all(|x| x.label != name) {
+                ret.push(CompletionItem {
+                    label: name,
+                    kind: Some(kind),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+    ret
+}
+
+/// Get the partial file name before byte offset `pos` if `pos` is in the string of `` `include``.
+fn get_include_prefix(s: &str, pos: usize) -> Option<&str> {
+    let line = s.get(..pos)?;
+    let line = &line[line.rfind('\n').map_or(0, |x| x + 1)..];
+    let rest = &line[line.rfind("`include")? + "`include".len()..];
+    let rest = rest.trim_start_matches([' ', '\t']).strip_prefix('"')?;
+    if rest.contains('"') {
+        return None;
+    }
+    Some(rest)
+}
+
+/// Get files and directories for the partial file name in `` `include`` at byte offset `pos`.
+///
+/// The directory of the partial name is searched in `include_paths`, and files with `extensions`
+/// are offered. Returns `None` if `pos` is not in the string of `` `include``.
+pub fn include_completions(
+    s: &str,
+    pos: usize,
+    include_paths: &[PathBuf],
+    extensions: &[String],
+) -> Option<Vec<CompletionItem>> {
+    let prefix = get_include_prefix(s, pos)?;
+    let dir = Path::new(&prefix[..prefix.rfind('/').map_or(0, |x| x + 1)]);
+    let mut ret: Vec<CompletionItem> = Vec::new();
+    for path in include_paths {
+        let entries = match std::fs::read_dir(path.join(dir)) {
+            Ok(x) => x,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let path = entry.path();
+            let (label, kind) = if name.starts_with('.') {
+                continue;
+            } else if path.is_dir() {
+                (format!("{}/", name), CompletionItemKind::Folder)
+            } else if path
+                .extension()
+                .is_some_and(|x| extensions.iter().any(|y| x == y.as_str()))
+            {
+                (name, CompletionItemKind::File)
+            } else {
+                continue;
+            };
+            // Files found earlier in the include paths shadow the others
+            if ret.iter().all(|x| x.label != label) {
+                ret.push(CompletionItem {
+                    label,
+                    kind: Some(kind),
+                    detail: Some(path.to_string_lossy().into_owned()),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+    ret.sort_by(|a, b| a.label.cmp(&b.label));
+    Some(ret)
+}
+
+/// Check whether the word before byte offset `pos` follows a backtick.
+fn is_directive(s: &str, pos: usize) -> bool {
+    let prefix = match s.get(..pos) {
+        Some(x) => x,
+        None => return false,
+    };
+    let word = prefix.trim_end_matches(|x: char| x.is_ascii_alphanumeric() || x == '_');
+    word.ends_with('`')
+}
+
+/// Get unconnected ports of the module instantiated at byte offset `pos`, which follows a `.` in
+/// the port connections.
+fn port_completions(syntax_tree: &SyntaxTree, s: &str, pos: usize) -> Option<Vec<CompletionItem>> {
+    let (module, connections) = get_instance(s, pos)?;
+    let connected = get_connected_ports(connections);
+    let ports = get_ports(syntax_tree, module)?;
+    let ret = ports
+        .into_iter()
+        .filter(|(name, _)| !connected.contains(&name.as_str()))
+        .map(|(name, detail)| CompletionItem {
+            label: name,
+            kind: Some(CompletionItemKind::Variable),
+            detail: Some(detail).filter(|x| !x.is_empty()),
+            ..Default::default()
+        })
+        .collect();
+    Some(ret)
+}
+
+fn is_word(x: char) -> bool {
+    x.is_ascii_alphanumeric() || x == '_' || x == '$'
+}
+
+/// Get the module name and the port connections before byte offset `pos` if `pos` is at a named
+/// port connection like `.name` of an instance.
+fn get_instance(s: &str, pos: usize) -> Option<(&str, &str)> {
+    let prefix = s.get(..pos)?.trim_end_matches(is_word);
+    let dot = prefix.strip_suffix('.')?.len();
+
+    // Find the parenthesis opening the port connections
+    let mut depth = 0;
+    let mut open = None;
+    for (i, c) in s[..dot].char_indices().rev() {
+        match c {
+            ')' => depth += 1,
+            '(' if depth == 0 => {
+                open = Some(i);
+                break;
+            }
+            '(' => depth -= 1,
+            ';' => return None,
+            _ => (),
+        }
+    }
+    let open = open?;
+
+    // Skip the instance name with optional unpacked dimensions, and parameter assignments
+    let mut rest = s[..open].trim_end();
+    while rest.ends_with(']') {
+        rest = rest[..skip_back(rest, '[', ']')?].trim_end();
+    }
+    let instance = rest.trim_end_matches(is_word);
+    if instance.len() == rest.len() {
+        return None;
+    }
+    let mut rest = instance.trim_end();
+    if rest.ends_with(')') {
+        rest = rest[..skip_back(rest, '(', ')')?].trim_end();
+        rest = rest.strip_suffix('#')?.trim_end();
+    }
+    let module = &rest[rest.trim_end_matches(is_word).len()..];
+    if module.is_empty() || KEYWORDS.contains(&module) {
+        return None;
+    }
+    Some((module, &s[open + 1..dot]))
+}
+
+/// Get the offset of `open` matching `close` at the end of `s`.
+fn skip_back(s: &str, open: char, close: char) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s.char_indices().rev() {
+        if c == close {
+            depth += 1;
+        } else if c == open {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i);
+            }
+        }
+    }
+    None
+}
+
+/// Get the port names of named port connections like `.name(x)`.
+fn get_connected_ports(s: &str) -> Vec<&str> {
+    s.match_indices('.')
+        .map(|(i, _)| {
+            let name = s[i + 1..].trim_start();
+            &name[..name.len() - name.trim_start_matches(is_word).len()]
+        })
+        .filter(|x| !x.is_empty())
+        .collect()
+}
+
+/// Get the ports of the module `name` with their directions and types.
+fn get_ports(syntax_tree: &SyntaxTree, name: &str) -> Option<Vec<(String, String)>> {
+    let module = syntax_tree.into_iter().find(|node| match node {
+        RefNode::ModuleDeclarationAnsi(_) | RefNode::ModuleDeclarationNonansi(_) => {
+            unwrap_node!(node.clone(), ModuleIdentifier)
+                .and_then(get_identifier)
+                .and_then(|x| syntax_tree.get_str(&x))
+                == Some(name)
+        }
+        _ => false,
+    })?;
+
+    let mut ret: Vec<(String, String)> = Vec::new();
+    for node in module {
+        let text = match node {
+            RefNode::AnsiPortDeclaration(x) => syntax_tree.get_str_trim(x),
+            RefNode::PortDeclaration(x) => syntax_tree.get_str_trim(x),
+            _ => continue,
+        };
+        let text = match text {
+            Some(x) => x,
+            None => continue,
+        };
+        let mut header = None;
+        for x in node {
+            if let RefNode::PortIdentifier(_) = x {
+                let port = match get_identifier(x).and_then(|x| syntax_tree.get_str(&x)) {
+                    Some(x) => x,
+                    None => continue,
+                };
+                // The direction and type are before the first port of the declaration
+                let header = header.get_or_insert_with(|| {
+                    let beg = text.find(port).unwrap_or(0);
+                    let header = text[..beg].trim();
+                    match ret.last() {
+                        // ANSI ports inherit them from the preceding port
+                        Some((_, prev)) if header.is_empty() => prev.clone(),
+                        _ => String::from(header),
+                    }
+                });
+                if ret.iter().all(|(x, _)| x != port) {
+                    ret.push((String::from(port), header.clone()));
+                }
+            }
+        }
+    }
+    Some(ret)
+}
+
+/// Collect identifiers declared in the current document.
+fn get_declared_identifiers(syntax_tree: &SyntaxTree) -> Vec<(String, CompletionItemKind)> {
+    let mut ret = Vec::new();
+    for node in syntax_tree {
+        let kind = match node {
+            RefNode::ModuleIdentifier(_) => CompletionItemKind::Module,
+            RefNode::InterfaceIdentifier(_) => CompletionItemKind::Interface,
+            RefNode::ProgramIdentifier(_) | RefNode::PackageIdentifier(_) => {
+                CompletionItemKind::Module
+            }
+            RefNode::ClassIdentifier(_) => CompletionItemKind::Class,
+            RefNode::FunctionIdentifier(_) | RefNode::TaskIdentifier(_) => {
+                CompletionItemKind::Function
+            }
+            RefNode::NetDeclAssignment(_)
+            | RefNode::VariableDeclAssignment(_)
+            | RefNode::PortIdentifier(_) => CompletionItemKind::Variable,
+            RefNode::ParamAssignment(_) => CompletionItemKind::Constant,
+            _ => continue,
+        };
+        let ident = match get_identifier(node) {
+            Some(x) => x,
+            None => continue,
+        };
+        if get_local_offset(syntax_tree, &ident).is_none() {
+            continue;
+        }
+        if let Some(name) = syntax_tree.get_str(&ident) {
+            if ret.iter().all(|(x, _)| x != name) {
+                ret.push((String::from(name), kind));
+            }
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use sv_parser::parse_sv_str;
+
+    #[test]
+    fn test_completions() {
+        let s = "module A #(parameter W = 1) (input clk);\n  logic [W-1:0] data;\n  `\nendmodule\n";
+        let syntax_tree = crate::parse(
+            "module A #(parameter W = 1) (input clk);\n  logic [W-1:0] data;\nendmodule\n",
+        );
+
+        let items = completions(Some(&syntax_tree), s, 0, &[], false);
+        let label = |items: &[CompletionItem], label: &str| {
+            items.iter().find(|x| x.label == label).and_then(|x| x.kind)
+        };
+        assert_eq!(
+            label(&items, "always_ff"),
+            Some(CompletionItemKind::Keyword)
+        );
+        assert_eq!(label(&items, "A"), Some(CompletionItemKind::Module));
+        assert_eq!(label(&items, "W"), Some(CompletionItemKind::Constant));
+        assert_eq!(label(&items, "clk"), Some(CompletionItemKind::Variable));
+        assert_eq!(label(&items, "data"), Some(CompletionItemKind::Variable));
+        assert_eq!(label(&items, "include"), Some(CompletionItemKind::Keyword));
+        assert_eq!(label(&items, "ifdef"), None);
+
+        let pos = s.find('`').unwrap() + 1;
+        let items = completions(Some(&syntax_tree), s, pos, &[], false);
+        assert_eq!(label(&items, "ifdef"), Some(CompletionItemKind::Keyword));
+        assert_eq!(label(&items, "module"), None);
+    }
+
+    #[test]
+    fn test_completions_snippet() {
+        let s = "module A;\n  al\nendmodule\n";
+        let pos = s.find("al").unwrap() + 2;
+        let snippet = |snippets| {
+            completions(None, s, pos, &[], snippets)
+                .into_iter()
+                .find(|x| x.label == "always_ff" && x.kind == Some(CompletionItemKind::Snippet))
+        };
+        let item = snippet(true).unwrap();
+        assert_eq!(item.insert_text_format, Some(InsertTextFormat::Snippet));
+        assert_eq!(
+            item.insert_text.as_deref(),
+            Some("always_ff @(posedge ${1:clk}) begin\n\t$0\nend")
+        );
+        assert!(snippet(false).is_none());
+    }
+
+    #[test]
+    fn test_completions_port() {
+        let header = "`include \"b.svh\"\nmodule A #(parameter W = 1) (input logic clk, rst, output logic [W-1:0] q);\nendmodule\n";
+        let dir = std::env::temp_dir().join("svls_test_completions_port");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("b.svh"),
+            "module B(a, b);\n  input a;\n  output [1:0] b;\nendmodule\n",
+        )
+        .unwrap();
+        let (syntax_tree, _) = parse_sv_str(
+            header,
+            PathBuf::from(""),
+            &HashMap::new(),
+            std::slice::from_ref(&dir),
+            false,
+            false,
+        )
+        .unwrap();
+
+        let s = format!(
+            "{}module C;\n  A #(.W(4)) u_a [1:0] (.clk(clk), .\n  B u_b(.b(x), .\n",
+            header
+        );
+        let items = |pos: usize| {
+            completions(Some(&syntax_tree), &s, pos, &[], false)
+                .into_iter()
+                .map(|x| (x.label, x.detail))
+                .collect::<Vec<_>>()
+        };
+        let pos = s.find("), .").unwrap() + 4;
+        assert_eq!(
+            items(pos),
+            vec![
+                (String::from("rst"), Some(String::from("input logic"))),
+                (
+                    String::from("q"),
+                    Some(String::from("output logic [W-1:0]"))
+                ),
+            ]
+        );
+        let pos = s.rfind('.').unwrap() + 1;
+        assert_eq!(
+            items(pos),
+            vec![(String::from("a"), Some(String::from("input")))]
+        );
+        // Parameter assignments are not port connections
+        let pos = s.find(".W").unwrap() + 1;
+        assert!(items(pos).iter().any(|(x, _)| x == "always_ff"));
+    }
+
+    #[test]
+    fn test_include_completions() {
+        let dir = std::env::temp_dir().join("svls_test_include_completions");
+        std::fs::create_dir_all(dir.join("a/sub")).unwrap();
+        std::fs::create_dir_all(dir.join("b")).unwrap();
+        std::fs::write(dir.join("a/x.svh"), "").unwrap();
+        std::fs::write(dir.join("a/y.txt"), "").unwrap();
+        std::fs::write(dir.join("a/sub/z.sv"), "").unwrap();
+        std::fs::write(dir.join("b/x.svh"), "").unwrap();
+        std::fs::write(dir.join("b/w.sv"), "").unwrap();
+        let include_paths = vec![dir.join("a"), dir.join("b")];
+        let extensions = vec![String::from("sv"), String::from("svh")];
+        let labels = |s: &str| {
+            include_completions(s, s.len(), &include_paths, &extensions).map(|x| {
+                x.into_iter()
+                    .map(|x| (x.label, x.kind.unwrap()))
+                    .collect::<Vec<_>>()
+            })
+        };
+        use CompletionItemKind::{File, Folder};
+        assert_eq!(
+            labels("`include \"x"),
+            Some(vec![
+                (String::from("sub/"), Folder),
+                (String::from("w.sv"), File),
+                (String::from("x.svh"), File),
+            ])
+        );
+        assert_eq!(
+            labels("module A;\n`include \"sub/"),
+            Some(vec![(String::from("z.sv"), File)])
+        );
+        assert_eq!(labels("`include \"x.svh\" "), None);
+        assert_eq!(labels("$display(\""), None);
+    }
+
+    #[test]
+    fn test_completions_macro() {
+        let s = "`define WIDTH 8\n`define MAX(a, b) ((a) > (b) ? (a) : (b))\nmodule A;\n  `\nendmodule\n";
+        let syntax_tree = crate::parse(
+            "`define WIDTH 8\n`define MAX(a, b) ((a) > (b) ? (a) : (b))\nmodule A;\nendmodule\n",
+        );
+
+        let defines = vec![String::from("DEBUG"), String::from("WIDTH=16")];
+        let pos = s.rfind('`').unwrap() + 1;
+        let items = completions(Some(&syntax_tree), s, pos, &defines, false);
+        let item = |label: &str| {
+            items
+                .iter()
+                .find(|x| x.label == label)
+                .map(|x| (x.kind.unwrap(), x.detail.as_deref()))
+        };
+        assert_eq!(item("DEBUG"), Some((CompletionItemKind::Constant, None)));
+        assert_eq!(
+            item("WIDTH"),
+            Some((CompletionItemKind::Constant, Some("8")))
+        );
+        assert_eq!(
+            item("MAX"),
+            Some((
+                CompletionItemKind::Constant,
+                Some("((a) > (b) ? (a) : (b))")
+            ))
+        );
+    }
+}
@@ -1,5 +1,8 @@
 use serde_derive::{Deserialize, Serialize};
-use std::path::PathBuf;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tower_lsp::lsp_types::{DiagnosticSeverity, DiagnosticTag, TextDocumentSyncKind};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Config {
@@ -13,6 +16,147 @@ pub struct Config {
 pub struct ConfigOption {
     #[serde(default = "default_as_true")]
     pub linter: bool,
+    #[serde(default = "default_debounce_ms")]
+    pub debounce_ms: u64,
+    #[serde(default)]
+    pub lint_on: LintOn,
+    #[serde(default)]
+    pub severity: HashMap<String, Severity>,
+    #[serde(default)]
+    pub tags: HashMap<String, Tag>,
+    #[serde(default)]
+    pub style_as_hint: bool,
+    #[serde(default = "default_rule_doc_base_url")]
+    pub rule_doc_base_url: String,
+    #[serde(default)]
+    pub log_level: Option<String>,
+    #[serde(default)]
+    pub log_file: Option<PathBuf>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default)]
+    pub include_patterns: Vec<String>,
+    #[serde(default = "default_as_true")]
+    pub report_parse_errors: bool,
+    #[serde(default)]
+    pub enable_rules: Vec<String>,
+    #[serde(default)]
+    pub disable_rules: Vec<String>,
+    #[serde(default = "default_header_extensions")]
+    pub header_extensions: Vec<String>,
+    #[serde(default)]
+    pub command_file: Option<PathBuf>,
+    #[serde(default)]
+    pub text_sync: TextSync,
+    #[serde(default)]
+    pub formatter: Vec<String>,
+    #[serde(default = "default_formatter_timeout_ms")]
+    pub formatter_timeout_ms: u64,
+    #[serde(default)]
+    pub lint_threads: Option<usize>,
+    #[serde(default)]
+    pub max_diagnostics_per_file: Option<usize>,
+    #[serde(default)]
+    pub compilation_order: Vec<PathBuf>,
+    #[serde(default = "default_as_true")]
+    pub diagnostics: bool,
+    #[serde(default = "default_as_true")]
+    pub lint_includes: bool,
+    #[serde(default)]
+    pub default_rules: DefaultRules,
+    #[serde(default)]
+    pub mode: Mode,
+    #[serde(default = "default_max_file_bytes")]
+    pub max_file_bytes: usize,
+    #[serde(default)]
+    pub ignore_include: bool,
+    #[serde(default)]
+    pub allow_incomplete: bool,
+}
+
+/// When documents are linted.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LintOn {
+    #[default]
+    Change,
+    Save,
+}
+
+/// What is checked while typing.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    #[default]
+    Lint,
+    /// Report parse errors only, and check lint rules on open and save.
+    Syntax,
+}
+
+/// Lint rules enabled if `.svlint.toml` can't be used.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DefaultRules {
+    #[default]
+    All,
+    None,
+    Recommended,
+}
+
+/// How document changes are synchronized from the client.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TextSync {
+    #[default]
+    Incremental,
+    Full,
+}
+
+impl From<TextSync> for TextDocumentSyncKind {
+    fn from(x: TextSync) -> Self {
+        match x {
+            TextSync::Incremental => TextDocumentSyncKind::Incremental,
+            TextSync::Full => TextDocumentSyncKind::Full,
+        }
+    }
+}
+
+/// Severity of diagnostics reported by a lint rule.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+impl From<Severity> for DiagnosticSeverity {
+    fn from(x: Severity) -> Self {
+        match x {
+            Severity::Error => DiagnosticSeverity::Error,
+            Severity::Warning => DiagnosticSeverity::Warning,
+            Severity::Information => DiagnosticSeverity::Information,
+            Severity::Hint => DiagnosticSeverity::Hint,
+        }
+    }
+}
+
+/// Tag of diagnostics reported by a lint rule, which changes how editors render them.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Tag {
+    Deprecated,
+    Unnecessary,
+}
+
+impl From<Tag> for DiagnosticTag {
+    fn from(x: Tag) -> Self {
+        match x {
+            Tag::Deprecated => DiagnosticTag::Deprecated,
+            Tag::Unnecessary => DiagnosticTag::Unnecessary,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -23,6 +167,84 @@ pub struct ConfigVerilog {
     pub defines: Vec<String>,
 }
 
+impl Config {
+    /// Overlay settings pushed by the client on top of this config.
+    ///
+    /// `settings` mirrors the layout of `.svls.toml`, and its values take precedence.
+    pub fn merge(&self, settings: &Value) -> Result<Config, serde_json::Error> {
+        let mut value = serde_json::to_value(self)?;
+        merge_value(&mut value, settings);
+        serde_json::from_value(value)
+    }
+
+    /// Append defines and include paths of `verilog`, whose defines take precedence.
+    pub fn append_verilog(&mut self, verilog: &ConfigVerilog) {
+        self.verilog.defines.extend(verilog.defines.iter().cloned());
+        for x in &verilog.include_paths {
+            if !self.verilog.include_paths.contains(x) {
+                self.verilog.include_paths.push(x.clone());
+            }
+        }
+    }
+
+    /// Merge `+define+` and `+incdir+` entries of `option.command_file` into `verilog`.
+    ///
+    /// A relative command file is resolved against `root`, and relative include directories
+    /// against the command file. Defines of this config take precedence.
+    pub fn apply_command_file(&mut self, root: Option<&Path>) -> Result<(), String> {
+        let path = match self.option.command_file {
+            Some(ref x) => match root {
+                Some(root) => root.join(x),
+                None => x.clone(),
+            },
+            None => return Ok(()),
+        };
+        let s = std::fs::read_to_string(&path)
+            .map_err(|_| format!("Failed to read {}.", path.to_string_lossy()))?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let (mut defines, include_paths) = parse_command_file(&s, dir);
+        defines.append(&mut self.verilog.defines);
+        self.verilog.defines = defines;
+        for x in include_paths {
+            if !self.verilog.include_paths.contains(&x) {
+                self.verilog.include_paths.push(x);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Extract defines and include directories from a command file given to simulators by `-f`.
+///
+/// Other arguments like source files are ignored.
+fn parse_command_file(s: &str, dir: &Path) -> (Vec<String>, Vec<PathBuf>) {
+    let mut defines = Vec::new();
+    let mut include_paths = Vec::new();
+    for line in s.lines() {
+        let line = line.split("//").next().unwrap();
+        let line = line.split('#').next().unwrap();
+        for arg in line.split_whitespace() {
+            if let Some(x) = arg.strip_prefix("+define+") {
+                defines.extend(x.split('+').filter(|x| !x.is_empty()).map(String::from));
+            } else if let Some(x) = arg.strip_prefix("+incdir+") {
+                include_paths.extend(x.split('+').filter(|x| !x.is_empty()).map(|x| dir.join(x)));
+            }
+        }
+    }
+    (defines, include_paths)
+}
+
+fn merge_value(base: &mut Value, other: &Value) {
+    match (base, other) {
+        (Value::Object(base), Value::Object(other)) => {
+            for (k, v) in other {
+                merge_value(base.entry(k.clone()).or_insert(Value::Null), v);
+            }
+        }
+        (base, other) => *base = other.clone(),
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         toml::from_str("").unwrap()
@@ -50,3 +272,187 @@ fn default_as_true() -> bool {
 fn default_as_false() -> bool {
     false
 }
+
+fn default_debounce_ms() -> u64 {
+    200
+}
+
+fn default_formatter_timeout_ms() -> u64 {
+    10000
+}
+
+fn default_max_file_bytes() -> usize {
+    2 * 1024 * 1024
+}
+
+fn default_rule_doc_base_url() -> String {
+    String::from("https://github.com/dalance/svlint/blob/master/RULES.md#")
+}
+
+fn default_header_extensions() -> Vec<String> {
+    vec![String::from("svh"), String::from("vh"), String::from("h")]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let config: Config = toml::from_str(
+            r#"
+            [option]
+            linter = true
+
+            [verilog]
+            include_paths = ["a"]
+            defines = ["A"]
+            "#,
+        )
+        .unwrap();
+        let settings = serde_json::json!({
+            "option": { "linter": false },
+            "verilog": { "include_paths": ["b", "c"] },
+        });
+        let config = config.merge(&settings).unwrap();
+        assert!(!config.option.linter);
+        assert_eq!(
+            config.verilog.include_paths,
+            vec![PathBuf::from("b"), PathBuf::from("c")]
+        );
+        assert_eq!(config.verilog.defines, vec![String::from("A")]);
+    }
+
+    #[test]
+    fn test_lint_on() {
+        let config = Config::default();
+        assert_eq!(config.option.lint_on, LintOn::Change);
+
+        let config: Config = toml::from_str("[option]\nlint_on = \"save\"").unwrap();
+        assert_eq!(config.option.lint_on, LintOn::Save);
+
+        assert!(toml::from_str::<Config>("[option]\nlint_on = \"open\"").is_err());
+    }
+
+    #[test]
+    fn test_mode() {
+        let config = Config::default();
+        assert_eq!(config.option.mode, Mode::Lint);
+
+        let config: Config = toml::from_str("[option]\nmode = \"syntax\"").unwrap();
+        assert_eq!(config.option.mode, Mode::Syntax);
+    }
+
+    #[test]
+    fn test_text_sync() {
+        let config = Config::default();
+        assert_eq!(config.option.text_sync, TextSync::Incremental);
+
+        let config: Config = toml::from_str("[option]\ntext_sync = \"full\"").unwrap();
+        assert_eq!(config.option.text_sync, TextSync::Full);
+        assert_eq!(
+            TextDocumentSyncKind::from(config.option.text_sync),
+            TextDocumentSyncKind::Full
+        );
+    }
+
+    #[test]
+    fn test_severity() {
+        let config: Config = toml::from_str(
+            r#"
+            [option.severity]
+            legacy_always = "error"
+            enum_with_type = "hint"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.option.severity.get("legacy_always"),
+            Some(&Severity::Error)
+        );
+        assert_eq!(
+            config.option.severity.get("enum_with_type"),
+            Some(&Severity::Hint)
+        );
+        assert_eq!(config.option.severity.get("default_nettype_none"), None);
+    }
+
+    #[test]
+    fn test_tags() {
+        let config: Config = toml::from_str(
+            r#"
+            [option.tags]
+            legacy_always = "deprecated"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.option.tags.get("legacy_always"),
+            Some(&Tag::Deprecated)
+        );
+        assert!(Config::default().option.tags.is_empty());
+        assert!(toml::from_str::<Config>("[option.tags]\nlegacy_always = \"faded\"").is_err());
+    }
+
+    #[test]
+    fn test_append_verilog() {
+        let mut config: Config =
+            toml::from_str("[verilog]\ninclude_paths = [\"a\"]\ndefines = [\"A=1\"]\n").unwrap();
+        let verilog = ConfigVerilog {
+            include_paths: vec![PathBuf::from("a"), PathBuf::from("/b")],
+            defines: vec![String::from("A=2")],
+        };
+        config.append_verilog(&verilog);
+        assert_eq!(
+            config.verilog.include_paths,
+            vec![PathBuf::from("a"), PathBuf::from("/b")]
+        );
+        assert_eq!(
+            config.verilog.defines,
+            vec![String::from("A=1"), String::from("A=2")]
+        );
+    }
+
+    #[test]
+    fn test_apply_command_file() {
+        let dir = std::env::temp_dir().join("svls_test_apply_command_file");
+        std::fs::create_dir_all(dir.join("sim")).unwrap();
+        std::fs::write(
+            dir.join("sim/files.f"),
+            "// flow settings\n+define+A=1+B\n+incdir+../include +incdir+/abs\n-sv top.sv # top\n+define+C\n",
+        )
+        .unwrap();
+        let mut config: Config = toml::from_str(
+            r#"
+            [option]
+            command_file = "sim/files.f"
+
+            [verilog]
+            include_paths = ["src"]
+            defines = ["A=2"]
+            "#,
+        )
+        .unwrap();
+        config.apply_command_file(Some(&dir)).unwrap();
+        assert_eq!(
+            config.verilog.defines,
+            vec![
+                String::from("A=1"),
+                String::from("B"),
+                String::from("C"),
+                String::from("A=2")
+            ]
+        );
+        assert_eq!(
+            config.verilog.include_paths,
+            vec![
+                PathBuf::from("src"),
+                dir.join("sim/../include"),
+                PathBuf::from("/abs")
+            ]
+        );
+
+        config.option.command_file = Some(PathBuf::from("missing.f"));
+        assert!(config.apply_command_file(Some(&dir)).is_err());
+    }
+}
@@ -0,0 +1,205 @@
+use crate::position::get_position;
+use crate::symbol::{get_identifier, get_location};
+use sv_parser::{Locate, RefNode, SyntaxTree};
+use tower_lsp::lsp_types::*;
+
+/// A macro defined by `` `define`` or the configuration.
+pub struct Macro {
+    pub name: String,
+    /// Formal arguments including the parentheses, like `(a, b)`.
+    pub arguments: Option<String>,
+    pub text: Option<String>,
+    /// The name in the `` `define`` directive, or `None` for the configuration.
+    pub locate: Option<Locate>,
+}
+
+/// Collect macros defined by `` `define`` in the order of definitions.
+pub fn get_macros(syntax_tree: &SyntaxTree) -> Vec<Macro> {
+    let mut ret = Vec::new();
+    for node in syntax_tree {
+        if let RefNode::TextMacroDefinition(x) = node {
+            let (_, _, ref name, ref text) = x.nodes;
+            let (ref ident, ref arguments) = name.nodes;
+            let ident = match get_identifier(ident.into()) {
+                Some(x) => x,
+                None => continue,
+            };
+            let name = match syntax_tree.get_str(&ident) {
+                Some(x) => String::from(x),
+                None => continue,
+            };
+            let arguments = arguments
+                .as_ref()
+                .and_then(|x| syntax_tree.get_str_trim(x))
+                .map(String::from);
+            let text = text
+                .as_ref()
+                .and_then(|x| syntax_tree.get_str(&x.nodes.0))
+                .map(|x| String::from(x.trim()));
+            ret.push(Macro {
+                name,
+                arguments,
+                text,
+                locate: Some(ident),
+            });
+        }
+    }
+    ret
+}
+
+/// Get macros given by the configuration in the form of `NAME` or `NAME=VALUE`.
+pub fn get_config_macros(defines: &[String]) -> Vec<Macro> {
+    defines
+        .iter()
+        .map(|define| {
+            let mut define = define.splitn(2, '=');
+            Macro {
+                name: String::from(define.next().unwrap()),
+                arguments: None,
+                text: define.next().map(String::from),
+                locate: None,
+            }
+        })
+        .collect()
+}
+
+/// Get the name and the byte offsets of the macro usage like `` `NAME`` at byte offset `pos`.
+///
+/// The offsets include the backtick.
+pub fn get_macro_usage(s: &str, pos: usize) -> Option<(&str, usize, usize)> {
+    let is_word = |x: char| x.is_ascii_alphanumeric() || x == '_' || x == '$';
+    let beg = s.get(..pos)?.trim_end_matches(is_word).len();
+    let end = pos + s.get(pos..)?.len() - s[pos..].trim_start_matches(is_word).len();
+    if beg == 0 || beg == end || !s[..beg].ends_with('`') {
+        return None;
+    }
+    Some((&s[beg..end], beg - 1, end))
+}
+
+/// Show the definition of the macro used at byte offset `pos` of the current document.
+///
+/// Definitions in the document take precedence over `defines` of the configuration.
+pub fn hover(
+    syntax_tree: Option<&SyntaxTree>,
+    s: &str,
+    pos: usize,
+    defines: &[String],
+) -> Option<Hover> {
+    let (name, beg, end) = get_macro_usage(s, pos)?;
+    let mut macros = get_config_macros(defines);
+    if let Some(syntax_tree) = syntax_tree {
+        macros.append(&mut get_macros(syntax_tree));
+    }
+    let x = macros.into_iter().rev().find(|x| x.name == name)?;
+
+    let mut value = format!("```systemverilog\n`define {}", x.name);
+    if let Some(ref arguments) = x.arguments {
+        value.push_str(arguments);
+    }
+    if let Some(ref text) = x.text {
+        value.push(' ');
+        value.push_str(text);
+    }
+    value.push_str("\n```");
+    let (line, col) = get_position(s, beg);
+    let (end_line, end_col) = get_position(s, end);
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }),
+        range: Some(Range::new(
+            Position::new(line, col),
+            Position::new(end_line, end_col),
+        )),
+    })
+}
+
+/// Find the `` `define`` of the macro used at byte offset `pos` of the current document.
+///
+/// The definition may be in an included file.
+pub fn goto_macro_definition(
+    syntax_tree: &SyntaxTree,
+    uri: &Url,
+    s: &str,
+    pos: usize,
+) -> Option<Location> {
+    let (name, _, _) = get_macro_usage(s, pos)?;
+    let x = get_macros(syntax_tree)
+        .into_iter()
+        .rev()
+        .find(|x| x.name == name)?;
+    get_location(syntax_tree, uri, s, &x.locate?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use sv_parser::parse_sv_str;
+
+    #[test]
+    fn test_hover() {
+        let s = "`define WIDTH 8\n`define MAX(a, b) ((a) > (b) ? (a) : (b))\nmodule A;\n  logic [`WIDTH-1:0] x = `MAX(`DEBUG, 1);\nendmodule\n";
+        // The buffer being typed is not parsed
+        let syntax_tree = crate::parse(&s.replace("`DEBUG", "0"));
+        let defines = vec![String::from("DEBUG=1"), String::from("WIDTH=16")];
+        let value = |pos: usize| {
+            hover(Some(&syntax_tree), s, pos, &defines).map(|x| match x.contents {
+                HoverContents::Markup(x) => x.value,
+                _ => unreachable!(),
+            })
+        };
+
+        let pos = s.rfind("`WIDTH").unwrap();
+        assert_eq!(
+            value(pos + 3).as_deref(),
+            Some("```systemverilog\n`define WIDTH 8\n```")
+        );
+        let range = hover(Some(&syntax_tree), s, pos + 6, &defines)
+            .unwrap()
+            .range
+            .unwrap();
+        assert_eq!(range, Range::new(Position::new(3, 9), Position::new(3, 15)));
+        assert_eq!(
+            value(s.rfind("MAX").unwrap()).as_deref(),
+            Some("```systemverilog\n`define MAX(a, b) ((a) > (b) ? (a) : (b))\n```")
+        );
+        assert_eq!(
+            value(s.rfind("DEBUG").unwrap()).as_deref(),
+            Some("```systemverilog\n`define DEBUG 1\n```")
+        );
+        assert_eq!(value(s.find("logic").unwrap()), None);
+        assert_eq!(value(s.rfind("endmodule").unwrap()), None);
+    }
+
+    #[test]
+    fn test_goto_macro_definition() {
+        let dir = std::env::temp_dir().join("svls_test_goto_macro_definition");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.svh"), "\n`define WIDTH 8\n").unwrap();
+        let s = "`include \"a.svh\"\n`define MAX(a, b) a\nmodule A;\n  logic [`WIDTH-1:0] x = `MAX(1, `DEBUG);\nendmodule\n";
+        let mut defines = HashMap::new();
+        defines.insert(String::from("DEBUG"), None);
+        let (syntax_tree, _) = parse_sv_str(
+            s,
+            PathBuf::from(""),
+            &defines,
+            std::slice::from_ref(&dir),
+            false,
+            false,
+        )
+        .unwrap();
+        let uri = Url::parse("file:///test.sv").unwrap();
+        let location = |name: &str| {
+            goto_macro_definition(&syntax_tree, &uri, s, s.rfind(name).unwrap())
+                .map(|x| (x.uri, x.range.start.line, x.range.start.character))
+        };
+        assert_eq!(location("MAX"), Some((uri.clone(), 1, 8)));
+        let header =
+            Url::from_file_path(std::fs::canonicalize(dir.join("a.svh")).unwrap()).unwrap();
+        assert_eq!(location("WIDTH"), Some((header, 1, 8)));
+        assert_eq!(location("DEBUG"), None);
+    }
+}
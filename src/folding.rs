@@ -0,0 +1,82 @@
+use crate::symbol::get_range;
+use sv_parser::{RefNode, SyntaxTree};
+use tower_lsp::lsp_types::*;
+
+/// Get folding ranges of blocks and multi-line block comments in the current document.
+pub fn folding_ranges(syntax_tree: &SyntaxTree, s: &str) -> Vec<FoldingRange> {
+    let mut ret: Vec<FoldingRange> = Vec::new();
+    for node in syntax_tree {
+        let kind = match node {
+            RefNode::ModuleDeclaration(_)
+            | RefNode::InterfaceDeclaration(_)
+            | RefNode::ProgramDeclaration(_)
+            | RefNode::PackageDeclaration(_)
+            | RefNode::ClassDeclaration(_)
+            | RefNode::FunctionDeclaration(_)
+            | RefNode::TaskDeclaration(_)
+            | RefNode::SeqBlock(_)
+            | RefNode::ParBlock(_)
+            | RefNode::GenerateBlockMultiple(_)
+            | RefNode::CaseStatement(_) => FoldingRangeKind::Region,
+            RefNode::Comment(x) => match syntax_tree.get_str(x) {
+                Some(x) if x.starts_with("/*") => FoldingRangeKind::Comment,
+                _ => continue,
+            },
+            _ => continue,
+        };
+        let range = match get_range(syntax_tree, s, node) {
+            Some(x) if x.end.line > x.start.line => x,
+            _ => continue,
+        };
+        if ret
+            .iter()
+            .any(|x| x.start_line == range.start.line && x.end_line == range.end.line)
+        {
+            continue;
+        }
+        ret.push(FoldingRange {
+            start_line: range.start.line,
+            start_character: None,
+            end_line: range.end.line,
+            end_character: None,
+            kind: Some(kind),
+        });
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_folding_ranges() {
+        let s = r##"/*
+ * header
+ */
+module A;
+  always_comb begin
+    case (a)
+      0: b = 0;
+      default: b = 1;
+    endcase
+  end
+  // single line
+endmodule
+"##;
+        let syntax_tree = crate::parse(s);
+        let ranges: Vec<_> = folding_ranges(&syntax_tree, s)
+            .into_iter()
+            .map(|x| (x.start_line, x.end_line, x.kind.unwrap()))
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![
+                (0, 2, FoldingRangeKind::Comment),
+                (3, 11, FoldingRangeKind::Region),
+                (4, 9, FoldingRangeKind::Region),
+                (5, 8, FoldingRangeKind::Region),
+            ]
+        );
+    }
+}
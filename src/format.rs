@@ -0,0 +1,123 @@
+use crate::position::get_position;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use tower_lsp::lsp_types::*;
+
+/// Pipe `s` through `command`, which is the program followed by its arguments, run in `dir`.
+///
+/// The command is killed and fails if it doesn't finish within `timeout`.
+pub fn run_formatter(
+    command: &[String],
+    s: &str,
+    dir: Option<&Path>,
+    timeout: Duration,
+) -> Result<String, String> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| String::from("option.formatter is empty"))?;
+    let mut cmd = Command::new(program);
+    cmd.args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(dir) = dir {
+        cmd.current_dir(dir);
+    }
+    let mut child = cmd
+        .spawn()
+        .map_err(|x| format!("Failed to run {}: {}", program, x))?;
+    // Write from another thread not to block on a formatter filling its stdout
+    let mut stdin = child.stdin.take().unwrap();
+    let input = String::from(s);
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let stdout = read_thread(child.stdout.take().unwrap());
+    let stderr = read_thread(child.stderr.take().unwrap());
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(x)) => break x,
+            Ok(None) if Instant::now() < deadline => {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!(
+                    "{} timed out after {} ms",
+                    program,
+                    timeout.as_millis()
+                ));
+            }
+            Err(x) => return Err(format!("Failed to run {}: {}", program, x)),
+        }
+    };
+    let _ = writer.join();
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    if !status.success() {
+        return Err(format!(
+            "{} failed with {}: {}",
+            program,
+            status,
+            String::from_utf8_lossy(&stderr).trim()
+        ));
+    }
+    String::from_utf8(stdout).map_err(|_| format!("{} returned non UTF-8 text", program))
+}
+
+/// Read `x` to the end in another thread.
+fn read_thread(mut x: impl Read + Send + 'static) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = x.read_to_end(&mut buf);
+        buf
+    })
+}
+
+/// Make an edit replacing the whole of `s` by `formatted`.
+pub fn full_edit(s: &str, formatted: String) -> TextEdit {
+    let (line, col) = get_position(s, s.len());
+    TextEdit::new(
+        Range::new(Position::new(0, 0), Position::new(line, col)),
+        formatted,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_formatter() {
+        let command = |x: &[&str]| x.iter().map(|x| String::from(*x)).collect::<Vec<_>>();
+        let s = "module A;\nendmodule\n";
+        let timeout = Duration::from_secs(10);
+        assert_eq!(
+            run_formatter(&command(&["tr", "a-z", "A-Z"]), s, None, timeout).as_deref(),
+            Ok("MODULE A;\nENDMODULE\n")
+        );
+        assert!(run_formatter(&command(&["false"]), s, None, timeout).is_err());
+        assert!(run_formatter(&command(&["svls-no-such-formatter"]), s, None, timeout).is_err());
+        assert!(run_formatter(&[], s, None, timeout).is_err());
+
+        let edit = full_edit(s, String::from("x"));
+        assert_eq!(
+            edit.range,
+            Range::new(Position::new(0, 0), Position::new(2, 0))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_formatter_timeout() {
+        let command = vec![String::from("sleep"), String::from("10")];
+        let begin = Instant::now();
+        let ret = run_formatter(&command, "", None, Duration::from_millis(100));
+        assert_eq!(ret, Err(String::from("sleep timed out after 100 ms")));
+        assert!(begin.elapsed() < Duration::from_secs(5));
+    }
+}
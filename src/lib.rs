@@ -0,0 +1,81 @@
+#![recursion_limit = "256"]
+
+pub mod backend;
+mod completion;
+pub mod config;
+mod define;
+mod folding;
+mod format;
+mod link;
+pub mod lint;
+pub mod logger;
+mod message;
+mod position;
+mod reference;
+mod selection;
+mod semantic;
+mod suppress;
+mod symbol;
+
+use config::Config;
+use std::path::PathBuf;
+use svlint::linter::Linter;
+use tower_lsp::lsp_types::{Diagnostic, Url};
+
+/// Parse `s` without defines and include paths, which must succeed, for tests.
+#[cfg(test)]
+pub(crate) fn parse(s: &str) -> sv_parser::SyntaxTree {
+    let (syntax_tree, _) = sv_parser::parse_sv_str(
+        s,
+        PathBuf::from(""),
+        &std::collections::HashMap::new(),
+        &[] as &[PathBuf],
+        false,
+        false,
+    )
+    .unwrap();
+    syntax_tree
+}
+
+/// Lint `source` by `linter` with the defines and include paths of `config`.
+///
+/// Include paths are resolved against the current directory, and diagnostics of included files
+/// are not returned. Related information refers to `source` by the URI `untitled:source`.
+pub fn lint_source(source: &str, config: &Config, linter: &mut Linter) -> Vec<Diagnostic> {
+    let uri = Url::parse("untitled:source").unwrap();
+    let include_paths: Vec<_> = config
+        .verilog
+        .include_paths
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+    let parsed = lint::parse(source, config, &include_paths);
+    let mut ret = lint::lint(&uri, source, &parsed, config, Some(linter), None);
+    lint::limit_file_diagnostics(&mut ret, config, None);
+    ret.remove(&uri).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use svlint::config::Config as LintConfig;
+    use tower_lsp::lsp_types::NumberOrString;
+
+    #[test]
+    fn test_lint_source() {
+        let mut lint_config = LintConfig::new();
+        lint_config.rules.legacy_always = true;
+        let mut linter = Linter::new(lint_config);
+        let s = "module A;\n  always @* begin\n  end\nendmodule\n";
+        let diags = lint_source(s, &Config::default(), &mut linter);
+        assert_eq!(diags.len(), 1);
+        assert_eq!(
+            diags[0].code,
+            Some(NumberOrString::String(String::from("legacy_always")))
+        );
+        let s = "module A;\n  assign a = b +* c;\nendmodule\n";
+        let diags = lint_source(s, &Config::default(), &mut linter);
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].message, "parse error");
+    }
+}
@@ -0,0 +1,141 @@
+use crate::position::get_position;
+use crate::symbol::get_file_uri;
+use std::path::{Path, PathBuf};
+use tower_lsp::lsp_types::*;
+
+/// Get links of the file names in `` `include`` directives of `s`.
+///
+/// Each file name is resolved against `include_paths` in order.
+/// A link without target is returned if the file is not found.
+pub fn document_links(s: &str, include_paths: &[PathBuf]) -> Vec<DocumentLink> {
+    let mut ret = Vec::new();
+    for (beg, end) in get_include_names(s) {
+        let name = &s[beg + 1..end - 1];
+        let (line, col) = get_position(s, beg);
+        let (end_line, end_col) = get_position(s, end);
+        ret.push(DocumentLink {
+            range: Range::new(Position::new(line, col), Position::new(end_line, end_col)),
+            target: resolve_include(Path::new(name), include_paths),
+            tooltip: None,
+            data: None,
+        });
+    }
+    ret
+}
+
+fn resolve_include(name: &Path, include_paths: &[PathBuf]) -> Option<Url> {
+    if name.is_absolute() {
+        return get_file_uri(name);
+    }
+    include_paths
+        .iter()
+        .map(|x| x.join(name))
+        .find(|x| x.is_file())
+        .and_then(|x| get_file_uri(&x))
+}
+
+/// Get byte ranges of the quoted file names following `` `include`` outside comments and strings.
+///
+/// The text is scanned by bytes, as slicing `s` may split a multi-byte character.
+fn get_include_names(s: &str) -> Vec<(usize, usize)> {
+    let mut ret = Vec::new();
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i..].starts_with(b"//") {
+            i = bytes[i..]
+                .iter()
+                .position(|x| *x == b'\n')
+                .map_or(bytes.len(), |x| i + x);
+        } else if bytes[i..].starts_with(b"/*") {
+            i = bytes[i + 2..]
+                .windows(2)
+                .position(|x| x == b"*/")
+                .map_or(bytes.len(), |x| i + x + 4);
+        } else if bytes[i] == b'"' {
+            i = skip_string(bytes, i);
+        } else if bytes[i..].starts_with(b"`include") {
+            i += "`include".len();
+            while i < bytes.len() && (bytes[i] == b' ' || bytes[i] == b'\t') {
+                i += 1;
+            }
+            let close = match bytes.get(i) {
+                Some(b'"') => b'"',
+                Some(b'<') => b'>',
+                _ => continue,
+            };
+            if let Some(x) = bytes[i + 1..]
+                .iter()
+                .take_while(|x| **x != b'\n')
+                .position(|x| *x == close)
+            {
+                ret.push((i, i + x + 2));
+                i += x + 2;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    ret
+}
+
+fn skip_string(bytes: &[u8], mut i: usize) -> usize {
+    i += 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return i + 1,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_include_names() {
+        let s = r##"`include "a.svh"
+// `include "b.svh"
+/* `include "c.svh" */
+`include <d.svh>
+string x = "`include \"e.svh\"";
+`include   "f.svh"
+"##;
+        let names: Vec<_> = get_include_names(s)
+            .into_iter()
+            .map(|(beg, end)| &s[beg..end])
+            .collect();
+        assert_eq!(names, vec!["\"a.svh\"", "<d.svh>", "\"f.svh\""]);
+    }
+
+    #[test]
+    fn test_get_include_names_non_ascii() {
+        let s = "module café;\n  (* note = \"é\" *) logic ü;\n`include \"a.svh\" // ß\nendmodule\n";
+        let names: Vec<_> = get_include_names(s)
+            .into_iter()
+            .map(|(beg, end)| &s[beg..end])
+            .collect();
+        assert_eq!(names, vec!["\"a.svh\""]);
+    }
+
+    #[test]
+    fn test_document_links() {
+        let dir = std::env::temp_dir().join("svls_test_document_links");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.svh"), "").unwrap();
+
+        let s = "module A;\n`include \"a.svh\"\n`include \"b.svh\"\nendmodule\n";
+        let links = document_links(s, std::slice::from_ref(&dir));
+        assert_eq!(links.len(), 2);
+        assert_eq!(
+            links[0].range,
+            Range::new(Position::new(1, 9), Position::new(1, 16))
+        );
+        assert_eq!(links[0].target, get_file_uri(&dir.join("a.svh")));
+        assert!(links[0].target.is_some());
+        assert_eq!(links[1].target, None);
+    }
+}
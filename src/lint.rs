@@ -0,0 +1,655 @@
+use crate::config::{Config, ConfigOption};
+use crate::message::{self, Message};
+use crate::position::{get_position, get_token_end};
+use crate::suppress::Suppression;
+use crate::symbol;
+use log::{debug, error};
+use serde_json::json;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use sv_parser::{parse_sv_str, Define, DefineText, Defines, NodeEvent, SyntaxTree};
+use svlint::linter::{LintFailed, Linter};
+use tower_lsp::lsp_types::*;
+
+/// Result of parsing a document.
+pub type Parsed = std::result::Result<(SyntaxTree, Defines), sv_parser::Error>;
+
+/// Get the defines of `config`.
+pub fn get_defines(config: &Config) -> Defines {
+    let mut defines = HashMap::new();
+    for define in &config.verilog.defines {
+        let mut define = define.splitn(2, '=');
+        let ident = String::from(define.next().unwrap());
+        let text = if let Some(x) = define.next() {
+            if let Ok(x) = enquote::unescape(x, None) {
+                Some(DefineText::new(x, None))
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+        let define = Define::new(ident.clone(), vec![], text);
+        defines.insert(ident, Some(define));
+    }
+    defines
+}
+
+/// Parse `s` with the defines of `config`, searching `include_paths` for included files.
+pub fn parse(s: &str, config: &Config, include_paths: &[PathBuf]) -> Parsed {
+    parse_with_defines(s, &get_defines(config), include_paths, &config.option)
+}
+
+/// Parse `s` with `defines`, searching `include_paths` for included files.
+///
+/// The parser flags are given by `option.ignore_include` and `option.allow_incomplete`.
+pub fn parse_with_defines(
+    s: &str,
+    defines: &Defines,
+    include_paths: &[PathBuf],
+    option: &ConfigOption,
+) -> Parsed {
+    debug!("include_paths: {:?}", include_paths);
+    debug!("defines: {:?}", defines);
+
+    // A panic is treated as a parse error without position, which is not reported
+    catch_panic("parse", || {
+        parse_sv_str(
+            s,
+            PathBuf::from(""),
+            defines,
+            include_paths,
+            option.ignore_include,
+            option.allow_incomplete,
+        )
+    })
+    .unwrap_or(Err(sv_parser::Error::Parse(None)))
+}
+
+/// Lint `s` as the content of `uri`, which is parsed to `parsed`.
+///
+/// The result always has an entry for `uri`, plus one for each included file with failures.
+/// Only parse errors are reported without `linter`, and nothing is reported if linting panics.
+/// Messages generated by svls are in `locale` of the client.
+pub fn lint(
+    uri: &Url,
+    s: &str,
+    parsed: &Parsed,
+    config: &Config,
+    linter: Option<&mut Linter>,
+    locale: Option<&str>,
+) -> HashMap<Url, Vec<Diagnostic>> {
+    catch_panic("lint", || {
+        lint_unchecked(uri, s, parsed, config, linter, locale)
+    })
+    .unwrap_or_else(|| vec![(uri.clone(), Vec::new())].into_iter().collect())
+}
+
+/// Text making linting panic, to test the recovery.
+#[cfg(test)]
+pub const PANIC_MARKER: &str = "svls_test_lint_panic";
+
+/// Text making the lint task panic outside `catch_panic`, to test the recovery.
+#[cfg(test)]
+pub const TASK_PANIC_MARKER: &str = "svls_test_task_panic";
+
+fn lint_unchecked(
+    uri: &Url,
+    s: &str,
+    parsed: &Parsed,
+    config: &Config,
+    linter: Option<&mut Linter>,
+    locale: Option<&str>,
+) -> HashMap<Url, Vec<Diagnostic>> {
+    #[cfg(test)]
+    if s.contains(PANIC_MARKER) {
+        panic!("{}", PANIC_MARKER);
+    }
+    let mut ret = HashMap::new();
+    ret.insert(uri.clone(), Vec::new());
+
+    match parsed {
+        Ok((syntax_tree, _new_defines)) => {
+            if let Some(linter) = linter {
+                let suppression = Suppression::new(s);
+                let mut included = HashMap::new();
+                for event in syntax_tree.into_iter().event() {
+                    let node = match event {
+                        NodeEvent::Enter(ref x) | NodeEvent::Leave(ref x) => x.clone(),
+                    };
+                    for failed in linter.check(syntax_tree, &event) {
+                        let (uri, text, suppression) = if failed.path == Path::new("") {
+                            (uri.clone(), s, &suppression)
+                        } else if !config.option.lint_includes {
+                            continue;
+                        } else {
+                            let (uri, text) = match included.entry(failed.path.clone()) {
+                                Entry::Occupied(x) => x.into_mut(),
+                                Entry::Vacant(x) => {
+                                    let text =
+                                        std::fs::read_to_string(&failed.path).ok().map(|x| {
+                                            let suppression = Suppression::new(&x);
+                                            (x, suppression)
+                                        });
+                                    let uri = symbol::get_file_uri(&failed.path);
+                                    x.insert((uri, text))
+                                }
+                            };
+                            match (uri, text) {
+                                (Some(uri), Some((text, suppression))) => {
+                                    (uri.clone(), text.as_str(), &*suppression)
+                                }
+                                _ => continue,
+                            }
+                        };
+                        let (line, col) = get_position(text, failed.beg);
+                        debug!(
+                            "failed: {} path: {} beg: {} len: {} line: {} col: {}",
+                            failed.name,
+                            failed.path.display(),
+                            failed.beg,
+                            failed.len,
+                            line,
+                            col
+                        );
+                        if suppression.is_suppressed(&failed.name, line) {
+                            debug!("suppressed: {}", failed.name);
+                            continue;
+                        }
+                        let (end_line, end_col) = get_position(text, failed.beg + failed.len);
+                        let severity = match config.option.severity.get(&failed.name) {
+                            Some(x) => (*x).into(),
+                            None if config.option.style_as_hint
+                                && get_category(&failed.name) == "style" =>
+                            {
+                                DiagnosticSeverity::Hint
+                            }
+                            None => DiagnosticSeverity::Warning,
+                        };
+                        let href = if config.option.rule_doc_base_url.is_empty() {
+                            None
+                        } else {
+                            let url = format!("{}{}", config.option.rule_doc_base_url, failed.name);
+                            Url::parse(&url).ok()
+                        };
+                        // Point at the keyword closing the construct flagged at its opening keyword
+                        let related = symbol::get_closing_keyword(
+                            syntax_tree,
+                            node.clone(),
+                            &failed.path,
+                            failed.beg,
+                        )
+                        .map(|(beg, len)| {
+                            let (line, col) = get_position(text, beg);
+                            let (end_line, end_col) = get_position(text, beg + len);
+                            let range = Range::new(
+                                Position::new(line, col),
+                                Position::new(end_line, end_col),
+                            );
+                            vec![DiagnosticRelatedInformation {
+                                location: Location::new(uri.clone(), range),
+                                message: message::get(
+                                    locale,
+                                    Message::ClosingKeyword,
+                                    &[&text[beg..beg + len]],
+                                ),
+                            }]
+                        });
+                        let tags = config
+                            .option
+                            .tags
+                            .get(&failed.name)
+                            .map(|x| vec![(*x).into()]);
+                        let message = get_message(&failed, locale);
+                        let mut diag = Diagnostic::new(
+                            Range::new(Position::new(line, col), Position::new(end_line, end_col)),
+                            Some(severity),
+                            Some(NumberOrString::String(failed.name.clone())),
+                            Some(String::from("svls")),
+                            message,
+                            related,
+                            tags,
+                        );
+                        diag.code_description = href.map(|href| CodeDescription { href });
+                        diag.data = Some(json!({
+                            "rule": failed.name,
+                            "category": get_category(&failed.name),
+                        }));
+                        ret.entry(uri).or_insert_with(Vec::new).push(diag);
+                    }
+                }
+            }
+        }
+        Err(x) => {
+            debug!("parse_error: {:?}", x);
+            // Headers are usually not complete by themselves
+            let header = Path::new(uri.path())
+                .extension()
+                .is_some_and(|x| config.option.header_extensions.iter().any(|y| x == &**y));
+            if !config.option.report_parse_errors || header {
+                return ret;
+            }
+            if let Some((path, pos)) = get_parse_error(x) {
+                let included;
+                let (uri, text) = if path == Path::new("") {
+                    (uri.clone(), s)
+                } else {
+                    included = std::fs::read_to_string(path).ok();
+                    match (symbol::get_file_uri(path), included.as_deref()) {
+                        (Some(uri), Some(text)) => (uri, text),
+                        _ => return ret,
+                    }
+                };
+                let (line, col) = get_position(text, pos);
+                let (end_line, end_col) = get_position(text, get_token_end(text, pos));
+                ret.entry(uri)
+                    .or_insert_with(Vec::new)
+                    .push(Diagnostic::new(
+                        Range::new(Position::new(line, col), Position::new(end_line, end_col)),
+                        Some(DiagnosticSeverity::Error),
+                        None,
+                        Some(String::from("svls")),
+                        message::get(locale, Message::ParseError, &[]),
+                        None,
+                        None,
+                    ));
+            }
+        }
+    }
+    for diags in ret.values_mut() {
+        dedup_diagnostics(diags);
+        sort_diagnostics(diags);
+    }
+    ret
+}
+
+/// Apply `option.max_diagnostics_per_file` to the diagnostics of each file.
+///
+/// This is done after all diagnostics are collected, so the summary is the last one of each file.
+pub fn limit_file_diagnostics(
+    ret: &mut HashMap<Url, Vec<Diagnostic>>,
+    config: &Config,
+    locale: Option<&str>,
+) {
+    if let Some(max) = config.option.max_diagnostics_per_file {
+        for diags in ret.values_mut() {
+            limit_diagnostics(diags, max, locale);
+        }
+    }
+}
+
+/// Run `f`, logging a panic inside it as an error instead of unwinding the caller.
+///
+/// Locks held by the caller are not poisoned because the panic doesn't cross them.
+pub fn catch_panic<T>(name: &str, f: impl FnOnce() -> T) -> Option<T> {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(x) => Some(x),
+        Err(x) => {
+            let message = x
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| x.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown panic");
+            error!("{}_panic: {}", name, message);
+            None
+        }
+    }
+}
+
+/// Get the origin of a parse error, looking through errors of included files.
+fn get_parse_error(x: &sv_parser::Error) -> Option<(&Path, usize)> {
+    match x {
+        sv_parser::Error::Parse(Some((path, pos))) => Some((path, *pos)),
+        sv_parser::Error::Include { source } => get_parse_error(source),
+        _ => None,
+    }
+}
+
+/// Remove diagnostics with the same range, code and message as a preceding one.
+pub fn dedup_diagnostics(diags: &mut Vec<Diagnostic>) {
+    let mut ret: Vec<Diagnostic> = Vec::new();
+    for diag in diags.drain(..) {
+        let duplicated = ret
+            .iter()
+            .any(|x| x.range == diag.range && x.code == diag.code && x.message == diag.message);
+        if !duplicated {
+            ret.push(diag);
+        }
+    }
+    *diags = ret;
+}
+
+/// Keep the first `max` diagnostics, followed by one telling how many are dropped.
+pub fn limit_diagnostics(diags: &mut Vec<Diagnostic>, max: usize, locale: Option<&str>) {
+    if diags.len() <= max {
+        return;
+    }
+    let dropped = diags.split_off(max);
+    let start = dropped[0].range.start;
+    diags.push(Diagnostic::new(
+        Range::new(start, start),
+        Some(DiagnosticSeverity::Information),
+        None,
+        Some(String::from("svls")),
+        message::get(
+            locale,
+            Message::DiagnosticsSuppressed,
+            &[&dropped.len().to_string()],
+        ),
+        None,
+        None,
+    ));
+}
+
+/// Rules catching likely bugs, and the others are about style.
+pub const FUNCTIONAL_RULES: &[&str] = &[
+    "blocking_assignment_in_always_ff",
+    "case_default",
+    "enum_with_type",
+    "function_same_as_system_function",
+    "function_with_automatic",
+    "legacy_always",
+    "level_sensitive_always",
+    "loop_variable_declaration",
+    "non_blocking_assignment_in_always_comb",
+];
+
+/// Get the category of the lint rule `name`, `"functional"` or `"style"`.
+pub fn get_category(name: &str) -> &'static str {
+    if FUNCTIONAL_RULES.contains(&name) {
+        "functional"
+    } else {
+        "style"
+    }
+}
+
+/// Get the message of `failed` prefixed by the rule name.
+///
+/// The reason is used if the hint is empty, and a generic message if both are empty.
+fn get_message(failed: &LintFailed, locale: Option<&str>) -> String {
+    let text = [&failed.hint, &failed.reason]
+        .iter()
+        .map(|x| x.trim())
+        .find(|x| !x.is_empty());
+    match text {
+        Some(x) => format!("{}: {}", failed.name, x),
+        None => {
+            let x = message::get(locale, Message::LintRuleFailed, &[&failed.name]);
+            format!("{}: {}", failed.name, x)
+        }
+    }
+}
+
+/// Sort diagnostics by their start positions and codes.
+pub fn sort_diagnostics(diags: &mut [Diagnostic]) {
+    diags.sort_by_cached_key(|x| {
+        let code = match x.code {
+            Some(NumberOrString::Number(ref x)) => x.to_string(),
+            Some(NumberOrString::String(ref x)) => x.clone(),
+            None => String::new(),
+        };
+        (x.range.start.line, x.range.start.character, code)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use svlint::config::Config as LintConfig;
+
+    /// Lint `s` with all rules of svlint enabled.
+    fn lint_all(s: &str, config: &Config) -> Vec<Diagnostic> {
+        let uri = Url::parse("file:///test.sv").unwrap();
+        let parsed = parse(s, config, &[]);
+        let mut linter = Linter::new(LintConfig::new().enable_all());
+        let mut diags = lint(&uri, s, &parsed, config, Some(&mut linter), None);
+        diags.remove(&uri).unwrap()
+    }
+
+    /// Find the diagnostic of the lint rule `rule` in `diags`.
+    fn find_rule<'a>(diags: &'a [Diagnostic], rule: &str) -> Option<&'a Diagnostic> {
+        let code = Some(NumberOrString::String(String::from(rule)));
+        diags.iter().find(|x| x.code == code)
+    }
+
+    #[test]
+    fn test_catch_panic() {
+        assert_eq!(catch_panic("test", || 1), Some(1));
+        assert_eq!(catch_panic("test", || -> i32 { panic!("broken") }), None);
+        // A lock held outside is still usable
+        let lock = std::sync::RwLock::new(0);
+        {
+            let mut w = lock.write().unwrap();
+            assert_eq!(catch_panic("test", || -> i32 { panic!("{}", *w) }), None);
+            *w += 1;
+        }
+        assert_eq!(*lock.read().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_lint_includes() {
+        let dir = std::env::temp_dir().join("svls_test_lint_includes");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.svh"), "module A;\n  reg a;\nendmodule\n").unwrap();
+        let uri = Url::from_file_path(dir.join("b.sv")).unwrap();
+        let header = Url::from_file_path(dir.join("a.svh")).unwrap();
+        let s = "`include \"a.svh\"\n";
+        let mut config = Config::default();
+        let parsed = parse(s, &config, std::slice::from_ref(&dir));
+        let mut linter = Linter::new(LintConfig::new().enable_all());
+
+        let diags = lint(&uri, s, &parsed, &config, Some(&mut linter), None);
+        assert!(diags[&header].iter().any(|x| x.range.start.line == 1));
+        config.option.lint_includes = false;
+        let diags = lint(&uri, s, &parsed, &config, Some(&mut linter), None);
+        assert!(!diags.contains_key(&header));
+    }
+
+    #[test]
+    fn test_lint_data() {
+        let s = "module A;\n  reg a;\n  always @* begin\n  end\nendmodule\n";
+        let diags = lint_all(s, &Config::default());
+        let data = |rule: &str| find_rule(&diags, rule)?.data.clone();
+        assert_eq!(
+            data("legacy_always"),
+            Some(json!({"rule": "legacy_always", "category": "functional"}))
+        );
+        assert_eq!(
+            data("wire_reg"),
+            Some(json!({"rule": "wire_reg", "category": "style"}))
+        );
+    }
+
+    #[test]
+    fn test_functional_rules() {
+        let rules = serde_json::to_value(LintConfig::new()).unwrap()["rules"].clone();
+        for name in FUNCTIONAL_RULES {
+            assert!(
+                rules.get(name).is_some(),
+                "{} is not a rule of svlint",
+                name
+            );
+        }
+        assert_eq!(get_category("legacy_always"), "functional");
+        assert_eq!(get_category("default_nettype_none"), "style");
+    }
+
+    #[test]
+    fn test_style_as_hint() {
+        let s = "module A;\n  reg a;\n  always @* begin\n  end\nendmodule\n";
+        let mut config: Config =
+            toml::from_str("[option.severity]\nlegacy_always = \"error\"").unwrap();
+        config.option.style_as_hint = true;
+        let diags = lint_all(s, &config);
+        let severity = |rule: &str| find_rule(&diags, rule)?.severity;
+        assert_eq!(severity("legacy_always"), Some(DiagnosticSeverity::Error));
+        assert_eq!(severity("wire_reg"), Some(DiagnosticSeverity::Hint));
+        assert_eq!(
+            severity("level_sensitive_always"),
+            Some(DiagnosticSeverity::Warning)
+        );
+    }
+
+    #[test]
+    fn test_lint_tags() {
+        let s = "module A;\n  reg a;\n  always @* begin\n  end\nendmodule\n";
+        let config: Config =
+            toml::from_str("[option.tags]\nlegacy_always = \"deprecated\"").unwrap();
+        let diags = lint_all(s, &config);
+        let tags = |rule: &str| find_rule(&diags, rule)?.tags.clone();
+        assert_eq!(tags("legacy_always"), Some(vec![DiagnosticTag::Deprecated]));
+        assert_eq!(tags("wire_reg"), None);
+    }
+
+    #[test]
+    fn test_parse_flags() {
+        let s = "`include \"missing.svh\"\nmodule A;\nendmodule\n";
+        let mut config = Config::default();
+        assert!(parse(s, &config, &[]).is_err());
+        config.option.ignore_include = true;
+        assert!(parse(s, &config, &[]).is_ok());
+
+        let s = "module A;\n  assign a = b;\n";
+        let mut config = Config::default();
+        assert!(parse(s, &config, &[]).is_err());
+        config.option.allow_incomplete = true;
+        assert!(parse(s, &config, &[]).is_ok());
+    }
+
+    #[test]
+    fn test_lint_crlf() {
+        let s = "module A;\n  reg a;\n  always @* begin\n  end\nendmodule\n";
+        let config = Config::default();
+        let ranges = |s: &str| {
+            let diags = lint_all(s, &config);
+            diags.iter().map(|x| x.range).collect::<Vec<_>>()
+        };
+        let lf = ranges(s);
+        assert!(!lf.is_empty());
+        assert_eq!(ranges(&s.replace('\n', "\r\n")), lf);
+        // Parse errors span to the end of the line
+        let s = "module A;\n  assign b = c +* d;\nendmodule\n";
+        let lf = ranges(s);
+        assert_eq!(lf.len(), 1);
+        assert_eq!(ranges(&s.replace('\n', "\r\n")), lf);
+    }
+
+    #[test]
+    fn test_get_message() {
+        let failed = |hint: &str, reason: &str| LintFailed {
+            path: PathBuf::from(""),
+            beg: 0,
+            len: 1,
+            name: String::from("legacy_always"),
+            hint: String::from(hint),
+            reason: String::from(reason),
+        };
+        assert_eq!(
+            get_message(&failed("`always_comb` must be used", "too loose"), None),
+            "legacy_always: `always_comb` must be used"
+        );
+        assert_eq!(
+            get_message(&failed("", "too loose"), None),
+            "legacy_always: too loose"
+        );
+        assert_eq!(
+            get_message(&failed(" ", ""), None),
+            "legacy_always: Lint rule legacy_always failed"
+        );
+    }
+
+    #[test]
+    fn test_dedup_diagnostics() {
+        let diagnostic = |line, code: &str, message: &str| {
+            Diagnostic::new(
+                Range::new(Position::new(line, 0), Position::new(line, 3)),
+                Some(DiagnosticSeverity::Warning),
+                Some(NumberOrString::String(String::from(code))),
+                Some(String::from("svls")),
+                String::from(message),
+                None,
+                None,
+            )
+        };
+        let mut diags = vec![
+            diagnostic(1, "a", "x"),
+            diagnostic(0, "a", "x"),
+            diagnostic(1, "a", "x"),
+            diagnostic(1, "b", "x"),
+            diagnostic(1, "a", "y"),
+            diagnostic(0, "a", "x"),
+        ];
+        dedup_diagnostics(&mut diags);
+        assert_eq!(
+            diags,
+            vec![
+                diagnostic(1, "a", "x"),
+                diagnostic(0, "a", "x"),
+                diagnostic(1, "b", "x"),
+                diagnostic(1, "a", "y"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_limit_diagnostics() {
+        let diagnostic = |line| {
+            Diagnostic::new(
+                Range::new(Position::new(line, 2), Position::new(line, 4)),
+                Some(DiagnosticSeverity::Warning),
+                None,
+                Some(String::from("svls")),
+                String::from("hint"),
+                None,
+                None,
+            )
+        };
+        let mut diags: Vec<_> = (0..5).map(diagnostic).collect();
+        limit_diagnostics(&mut diags, 5, None);
+        assert_eq!(diags.len(), 5);
+        limit_diagnostics(&mut diags, 2, None);
+        assert_eq!(diags.len(), 3);
+        assert_eq!(diags[1], diagnostic(1));
+        assert_eq!(diags[2].message, "3 more diagnostics suppressed");
+        assert_eq!(diags[2].severity, Some(DiagnosticSeverity::Information));
+        assert_eq!(
+            diags[2].range,
+            Range::new(Position::new(2, 2), Position::new(2, 2))
+        );
+    }
+
+    #[test]
+    fn test_sort_diagnostics() {
+        let diagnostic = |line, character, code: Option<&str>| {
+            Diagnostic::new(
+                Range::new(Position::new(line, character), Position::new(line + 1, 0)),
+                Some(DiagnosticSeverity::Warning),
+                code.map(|x| NumberOrString::String(String::from(x))),
+                Some(String::from("svls")),
+                String::from("hint"),
+                None,
+                None,
+            )
+        };
+        let mut diags = vec![
+            diagnostic(2, 0, Some("a")),
+            diagnostic(0, 4, Some("b")),
+            diagnostic(0, 4, Some("a")),
+            diagnostic(1, 0, None),
+            diagnostic(0, 1, Some("c")),
+        ];
+        sort_diagnostics(&mut diags);
+        assert_eq!(
+            diags,
+            vec![
+                diagnostic(0, 1, Some("c")),
+                diagnostic(0, 4, Some("a")),
+                diagnostic(0, 4, Some("b")),
+                diagnostic(1, 0, None),
+                diagnostic(2, 0, Some("a")),
+            ]
+        );
+    }
+}
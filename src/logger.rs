@@ -0,0 +1,70 @@
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+use serde_json::json;
+use std::io::Write;
+use std::sync::Mutex;
+
+/// Logger writing a JSON object per line.
+pub struct JsonLogger<W: Write + Send> {
+    level: LevelFilter,
+    writer: Mutex<W>,
+}
+
+impl<W: Write + Send + 'static> JsonLogger<W> {
+    pub fn init(level: LevelFilter, writer: W) -> Result<(), SetLoggerError> {
+        log::set_max_level(level);
+        log::set_boxed_logger(Box::new(JsonLogger {
+            level,
+            writer: Mutex::new(writer),
+        }))
+    }
+}
+
+impl<W: Write + Send> Log for JsonLogger<W> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let mut writer = self.writer.lock().unwrap();
+            let _ = writeln!(writer, "{}", format_record(record));
+        }
+    }
+
+    fn flush(&self) {
+        let _ = self.writer.lock().unwrap().flush();
+    }
+}
+
+fn format_record(record: &Record) -> String {
+    let timestamp = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+    json!({
+        "timestamp": timestamp,
+        "level": record.level().to_string(),
+        "target": record.target(),
+        "message": record.args().to_string(),
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Level;
+
+    #[test]
+    fn test_format_record() {
+        let line = format_record(
+            &Record::builder()
+                .level(Level::Debug)
+                .target("svls::backend")
+                .args(format_args!("lint: \"{}\"", "a.sv"))
+                .build(),
+        );
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["level"], "DEBUG");
+        assert_eq!(value["target"], "svls::backend");
+        assert_eq!(value["message"], "lint: \"a.sv\"");
+        assert!(value["timestamp"].as_str().unwrap().ends_with('Z'));
+    }
+}
@@ -1,11 +1,19 @@
-mod backend;
-mod config;
+#![recursion_limit = "256"]
 
-use backend::Backend;
 use log::debug;
+use serde_json::json;
 use simplelog::{Config, LevelFilter, WriteLogger};
+use std::env;
 use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process;
 use structopt::{clap, StructOpt};
+use svls::backend::{lint_file, Backend};
+use svls::config::ConfigVerilog;
+use svls::logger::JsonLogger;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tower_lsp::lsp_types::{DiagnosticSeverity, NumberOrString};
 use tower_lsp::{LspService, Server};
 
 // -------------------------------------------------------------------------------------------------
@@ -14,12 +22,63 @@ use tower_lsp::{LspService, Server};
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "svls")]
-#[structopt(long_version(option_env!("LONG_VERSION").unwrap_or(env!("CARGO_PKG_VERSION"))))]
+#[structopt(long_version(env!("LONG_VERSION")))]
 #[structopt(setting(clap::AppSettings::ColoredHelp))]
 pub struct Opt {
     /// Debug mode
     #[structopt(short = "d", long = "debug")]
     pub debug: bool,
+
+    /// Format of the debug log
+    #[structopt(long = "log-format", default_value = "text", possible_values = &["text", "json"])]
+    pub log_format: String,
+
+    /// Path to .svls.toml instead of searching it
+    #[structopt(long = "config", env = "SVLS_CONFIG", parse(from_os_str))]
+    pub config: Option<PathBuf>,
+
+    /// Path to .svlint.toml instead of searching it
+    #[structopt(long = "svlint-config", env = "SVLINT_CONFIG", parse(from_os_str))]
+    pub svlint_config: Option<PathBuf>,
+
+    /// Communicate over stdio (default)
+    #[structopt(long = "stdio", conflicts_with_all = &["listen", "pipe"])]
+    pub stdio: bool,
+
+    /// Listen on the given address (e.g. 127.0.0.1:9257) for a client instead of stdio
+    #[structopt(long = "listen")]
+    pub listen: Option<String>,
+
+    /// Connect to the given named pipe (Unix domain socket on non-Windows) instead of stdio
+    #[structopt(long = "pipe", conflicts_with = "listen")]
+    pub pipe: Option<String>,
+
+    /// Define a macro like NAME or NAME=VALUE, which overrides defines of .svls.toml
+    #[structopt(long = "define", number_of_values = 1)]
+    pub defines: Vec<String>,
+
+    /// Add an include path, which is searched after include paths of .svls.toml
+    #[structopt(long = "include-path", parse(from_os_str), number_of_values = 1)]
+    pub include_paths: Vec<PathBuf>,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Lint a file and print the diagnostics without starting the server
+    ///
+    /// The exit code is 1 if any error is found.
+    Lint {
+        /// File to lint
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+
+        /// Format of the diagnostics
+        #[structopt(long = "format", default_value = "text", possible_values = &["text", "json"])]
+        format: String,
+    },
 }
 
 // -------------------------------------------------------------------------------------------------
@@ -30,22 +89,154 @@ pub struct Opt {
 async fn main() {
     let opt = Opt::from_args();
 
+    // Include paths are relative to the current directory instead of the workspace root
+    let current_dir = env::current_dir().unwrap_or_default();
+    let verilog = ConfigVerilog {
+        include_paths: opt
+            .include_paths
+            .iter()
+            .map(|x| current_dir.join(x))
+            .collect(),
+        defines: opt.defines,
+    };
+
+    if let Some(Command::Lint { file, format }) = opt.command {
+        process::exit(lint(
+            &file,
+            &format,
+            opt.config,
+            opt.svlint_config,
+            &verilog,
+        ));
+    }
+
     if opt.debug {
-        let _ = WriteLogger::init(
-            LevelFilter::Debug,
-            Config::default(),
-            File::create("svls.log").unwrap(),
-        );
+        let file = File::create("svls.log").unwrap();
+        if opt.log_format == "json" {
+            let _ = JsonLogger::init(LevelFilter::Debug, file);
+        } else {
+            let _ = WriteLogger::init(LevelFilter::Debug, Config::default(), file);
+        }
     }
 
     debug!("start");
 
-    let stdin = tokio::io::stdin();
-    let stdout = tokio::io::stdout();
+    let config = opt.config;
+    let svlint_config = opt.svlint_config;
+    let (service, messages) =
+        LspService::new(|client| Backend::new(client, config, svlint_config, verilog));
+
+    let (read, write): (Box<dyn AsyncRead + Unpin>, Box<dyn AsyncWrite + Unpin>) =
+        if let Some(addr) = opt.listen {
+            let listener = match TcpListener::bind(&addr).await {
+                Ok(x) => x,
+                Err(x) => {
+                    eprintln!("Failed to listen on {}: {}", addr, x);
+                    process::exit(1);
+                }
+            };
+            let (stream, peer) = match listener.accept().await {
+                Ok(x) => x,
+                Err(x) => {
+                    eprintln!("Failed to accept a connection: {}", x);
+                    process::exit(1);
+                }
+            };
+            debug!("accepted: {}", peer);
 
-    let (service, messages) = LspService::new(|client| Backend::new(client));
-    Server::new(stdin, stdout)
+            let (read, write) = stream.into_split();
+            (Box::new(read), Box::new(write))
+        } else if let Some(name) = opt.pipe {
+            match connect_pipe(&name).await {
+                Ok(x) => x,
+                Err(x) => {
+                    eprintln!("Failed to connect to {}: {}", name, x);
+                    process::exit(1);
+                }
+            }
+        } else {
+            (Box::new(tokio::io::stdin()), Box::new(tokio::io::stdout()))
+        };
+
+    Server::new(read, write)
         .interleave(messages)
         .serve(service)
         .await;
 }
+
+/// Lint `file` and print the diagnostics in `format`, returning the exit code.
+fn lint(
+    file: &Path,
+    format: &str,
+    config: Option<PathBuf>,
+    svlint_config: Option<PathBuf>,
+    verilog: &ConfigVerilog,
+) -> i32 {
+    let root = env::current_dir().unwrap_or_default();
+    let path = root.join(file);
+    let (diags, warnings) = match lint_file(&path, &root, config, svlint_config, verilog) {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!("{}", x);
+            return 2;
+        }
+    };
+    for warning in warnings {
+        eprintln!("{}", warning);
+    }
+
+    if format == "json" {
+        let output = json!({ "file": file, "diagnostics": diags });
+        println!("{}", output);
+    } else {
+        for diag in &diags {
+            let severity = match diag.severity {
+                Some(DiagnosticSeverity::Error) => "error",
+                Some(DiagnosticSeverity::Warning) => "warning",
+                Some(DiagnosticSeverity::Information) => "information",
+                _ => "hint",
+            };
+            let code = match diag.code {
+                Some(NumberOrString::String(ref x)) => format!(" [{}]", x),
+                Some(NumberOrString::Number(x)) => format!(" [{}]", x),
+                None => String::new(),
+            };
+            println!(
+                "{}:{}:{}: {}: {}{}",
+                file.to_string_lossy(),
+                diag.range.start.line + 1,
+                diag.range.start.character + 1,
+                severity,
+                diag.message,
+                code
+            );
+        }
+    }
+
+    let error = diags
+        .iter()
+        .any(|x| x.severity == Some(DiagnosticSeverity::Error));
+    if error {
+        1
+    } else {
+        0
+    }
+}
+
+#[cfg(unix)]
+async fn connect_pipe(
+    name: &str,
+) -> std::io::Result<(Box<dyn AsyncRead + Unpin>, Box<dyn AsyncWrite + Unpin>)> {
+    let stream = tokio::net::UnixStream::connect(name).await?;
+    let (read, write) = stream.into_split();
+    Ok((Box::new(read), Box::new(write)))
+}
+
+#[cfg(windows)]
+async fn connect_pipe(
+    name: &str,
+) -> std::io::Result<(Box<dyn AsyncRead + Unpin>, Box<dyn AsyncWrite + Unpin>)> {
+    let client = tokio::net::windows::named_pipe::ClientOptions::new().open(name)?;
+    let (read, write) = tokio::io::split(client);
+    Ok((Box::new(read), Box::new(write)))
+}
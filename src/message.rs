@@ -0,0 +1,94 @@
+//! Messages of diagnostics generated by svls, looked up by the locale of the client.
+//!
+//! Messages of lint rules come from svlint and are not translated.
+
+/// A message generated by svls.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Message {
+    ParseError,
+    LintRuleFailed,
+    ClosingKeyword,
+    DiagnosticsSuppressed,
+    FileTooLarge,
+    DuplicateModule,
+    DuplicatePackage,
+    OtherDeclaration,
+}
+
+/// Messages of each locale, where each `{}` is replaced by an argument in order.
+///
+/// The first locale is used for messages missing in the others.
+const MESSAGES: &[(&str, &[(Message, &str)])] = &[(
+    "en",
+    &[
+        (Message::ParseError, "parse error"),
+        (Message::LintRuleFailed, "Lint rule {} failed"),
+        (Message::ClosingKeyword, "`{}` closing this construct"),
+        (
+            Message::DiagnosticsSuppressed,
+            "{} more diagnostics suppressed",
+        ),
+        (
+            Message::FileTooLarge,
+            "The file is not linted because it is larger than max_file_bytes ({} > {} bytes).",
+        ),
+        (
+            Message::DuplicateModule,
+            "Module `{}` is declared in multiple files",
+        ),
+        (
+            Message::DuplicatePackage,
+            "Package `{}` is declared in multiple files",
+        ),
+        (Message::OtherDeclaration, "`{}` is also declared here"),
+    ],
+)];
+
+fn find(locale: &str, message: Message) -> Option<&'static str> {
+    let (_, table) = MESSAGES
+        .iter()
+        .find(|(x, _)| x.eq_ignore_ascii_case(locale))?;
+    table.iter().find(|(x, _)| *x == message).map(|(_, x)| *x)
+}
+
+/// Get `message` in `locale` like `en-US` with `args`.
+///
+/// The language without the region is tried next, and English is used if neither is found.
+pub fn get(locale: Option<&str>, message: Message, args: &[&str]) -> String {
+    let text = locale
+        .and_then(|locale| {
+            let language = locale.split(['-', '_']).next().unwrap_or(locale);
+            find(locale, message).or_else(|| find(language, message))
+        })
+        .or_else(|| find(MESSAGES[0].0, message))
+        .unwrap_or_default();
+
+    let mut ret = String::new();
+    let mut args = args.iter();
+    for (i, part) in text.split("{}").enumerate() {
+        if i != 0 {
+            ret.push_str(args.next().copied().unwrap_or_default());
+        }
+        ret.push_str(part);
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get() {
+        assert_eq!(get(None, Message::ParseError, &[]), "parse error");
+        assert_eq!(
+            get(Some("en-US"), Message::DuplicateModule, &["A"]),
+            "Module `A` is declared in multiple files"
+        );
+        // Unknown locales fall back to English
+        assert_eq!(
+            get(Some("ja"), Message::FileTooLarge, &["3", "2"]),
+            "The file is not linted because it is larger than max_file_bytes (3 > 2 bytes)."
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use tower_lsp::lsp_types::Position;
+
+/// Get the line and the UTF-16 column of byte offset `pos`.
+///
+/// `\r\n` is a single line break, so the `\r` is not counted as a column.
+pub fn get_position(s: &str, pos: usize) -> (u32, u32) {
+    let mut line = 0;
+    let mut col = 0;
+    for (p, c) in s.char_indices() {
+        if p >= pos {
+            break;
+        }
+        if c == '\n' {
+            line += 1;
+            col = 0;
+        } else if c != '\r' || !s[p + 1..].starts_with('\n') {
+            col += c.len_utf16() as u32;
+        }
+    }
+    (line, col)
+}
+
+pub fn get_offset(s: &str, pos: &Position) -> usize {
+    let mut line_beg = 0;
+    for _ in 0..pos.line {
+        match s[line_beg..].find('\n') {
+            Some(x) => line_beg += x + 1,
+            None => return s.len(),
+        }
+    }
+    let line_end = match s[line_beg..].find('\n') {
+        Some(x) => line_beg + x,
+        None => s.len(),
+    };
+    let line = s[line_beg..line_end].trim_end_matches('\r');
+    line_beg + utf16_to_byte_offset(line, pos.character)
+}
+
+/// Convert the UTF-16 column `utf16_col` of `line_text` to a byte offset.
+///
+/// A column inside a surrogate pair is rounded up to the next character, and a column past
+/// the end is clamped to the end of the line.
+pub fn utf16_to_byte_offset(line_text: &str, utf16_col: u32) -> usize {
+    let mut col = 0;
+    for (p, c) in line_text.char_indices() {
+        if col >= utf16_col {
+            return p;
+        }
+        col += c.len_utf16() as u32;
+    }
+    line_text.len()
+}
+
+/// Get the byte offset of the end of the line at `pos`, excluding the line break.
+pub fn get_line_end(s: &str, pos: usize) -> usize {
+    match s.get(pos..).and_then(|x| x.find('\n')) {
+        Some(x) if s[pos..pos + x].ends_with('\r') => pos + x - 1,
+        Some(x) => pos + x,
+        None => s.len(),
+    }
+}
+
+/// Get the byte offset of the end of the whitespace-delimited token at `pos`.
+///
+/// The end of the line is returned if there is no token at `pos`.
+pub fn get_token_end(s: &str, pos: usize) -> usize {
+    match s.get(pos..).and_then(|x| x.find(char::is_whitespace)) {
+        Some(0) => get_line_end(s, pos),
+        Some(x) => pos + x,
+        None => s.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_position_utf16() {
+        let s = "/* 😀 */ reg a;\n";
+        let beg = s.find("reg").unwrap();
+        assert_eq!(beg, 11);
+        assert_eq!(get_position(s, beg), (0, 9));
+    }
+
+    #[test]
+    fn test_get_position_after_tab() {
+        let s = "module A;\n\t/* é */ reg a;\nendmodule\n";
+        let beg = s.find("reg").unwrap();
+        assert_eq!(get_position(s, beg), (1, 9));
+        assert_eq!(get_position(s, beg + "reg".len()), (1, 12));
+    }
+
+    #[test]
+    fn test_get_position_crlf() {
+        let s = "module A;\r\n  reg a;\r\nendmodule\r\n";
+        assert_eq!(get_position(s, s.find('\r').unwrap()), (0, 9));
+        assert_eq!(get_position(s, s.find('\n').unwrap()), (0, 9));
+        assert_eq!(get_position(s, s.find("reg").unwrap()), (1, 2));
+        assert_eq!(get_position(s, s.len()), (3, 0));
+        // A lone `\r` is not a line break
+        assert_eq!(get_position("a\rb", 3), (0, 3));
+
+        assert_eq!(get_line_end(s, 0), 9);
+        assert_eq!(get_line_end(s, 9), 9);
+        assert_eq!(
+            get_offset(s, &Position::new(1, 100)),
+            s.find(";\r\nend").unwrap() + 1
+        );
+    }
+
+    #[test]
+    fn test_get_position_tab() {
+        // A tab is a single column without expansion, as the LSP specification requires
+        let s = "module A;\n\t\treg a;\n \t\treg b;\nendmodule\n";
+        let found: Vec<_> = s
+            .match_indices("reg")
+            .map(|(x, _)| get_position(s, x))
+            .collect();
+        assert_eq!(found, vec![(1, 2), (2, 3)]);
+
+        for (p, _) in s.char_indices() {
+            let (line, col) = get_position(s, p);
+            assert_eq!(get_offset(s, &Position::new(line, col)), p);
+        }
+    }
+
+    #[test]
+    fn test_utf16_to_byte_offset() {
+        let s = "a😀b";
+        assert_eq!(utf16_to_byte_offset(s, 0), 0);
+        assert_eq!(utf16_to_byte_offset(s, 1), 1);
+        assert_eq!(utf16_to_byte_offset(s, 2), 5);
+        assert_eq!(utf16_to_byte_offset(s, 3), 5);
+        assert_eq!(utf16_to_byte_offset(s, 4), 6);
+        assert_eq!(utf16_to_byte_offset(s, 100), 6);
+
+        // "e" followed by U+0301 COMBINING ACUTE ACCENT
+        let s = "e\u{301}x";
+        assert_eq!(utf16_to_byte_offset(s, 1), 1);
+        assert_eq!(utf16_to_byte_offset(s, 2), 3);
+        assert_eq!(utf16_to_byte_offset(s, 3), 4);
+
+        // Every character boundary survives the round trip
+        let s = "é😀\u{301}a";
+        for (p, _) in s.char_indices() {
+            let col = s[..p].encode_utf16().count() as u32;
+            assert_eq!(utf16_to_byte_offset(s, col), p);
+        }
+    }
+
+    #[test]
+    fn test_get_offset_utf16() {
+        let s = "😀a\r\n/* é */ b\n";
+        assert_eq!(get_offset(s, &Position::new(0, 2)), 4);
+        assert_eq!(get_offset(s, &Position::new(0, 5)), 5);
+        assert_eq!(get_offset(s, &Position::new(1, 4)), 12);
+        assert_eq!(get_position(s, get_offset(s, &Position::new(1, 7))), (1, 7));
+    }
+
+    #[test]
+    fn test_get_token_end() {
+        let s = "assign a = b +* c;\n  \nx";
+        assert_eq!(get_token_end(s, 13), 15);
+        assert_eq!(get_token_end(s, 19), 21);
+        assert_eq!(get_token_end(s, 22), 23);
+    }
+}
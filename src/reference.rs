@@ -0,0 +1,427 @@
+use crate::completion::KEYWORDS;
+use crate::symbol::{get_identifier, get_local_offset, get_range, get_span};
+use std::collections::HashMap;
+use sv_parser::{Locate, RefNode, SyntaxTree};
+use tower_lsp::lsp_types::*;
+
+/// Get the identifier at byte offset `pos` of the current document.
+fn get_identifier_at(syntax_tree: &SyntaxTree, pos: usize) -> Option<Locate> {
+    for node in syntax_tree {
+        let locate = match node {
+            RefNode::SimpleIdentifier(x) => x.nodes.0,
+            RefNode::EscapedIdentifier(x) => x.nodes.0,
+            _ => continue,
+        };
+        if let Some(beg) = get_local_offset(syntax_tree, &locate) {
+            if beg <= pos && pos <= beg + locate.len {
+                return Some(locate);
+            }
+        }
+    }
+    None
+}
+
+/// Get the innermost module, interface, program, package or class containing byte offset `pos`.
+///
+/// The whole document is returned if there is no such declaration.
+fn get_scope(syntax_tree: &SyntaxTree, pos: usize) -> RefNode<'_> {
+    let mut ret = None;
+    for node in syntax_tree {
+        match node {
+            RefNode::ModuleDeclaration(_)
+            | RefNode::InterfaceDeclaration(_)
+            | RefNode::ProgramDeclaration(_)
+            | RefNode::PackageDeclaration(_)
+            | RefNode::ClassDeclaration(_) => {
+                if let Some((beg, end)) = get_span(syntax_tree, node.clone()) {
+                    if beg <= pos && pos <= end {
+                        ret = Some(node);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    ret.unwrap_or_else(|| syntax_tree.into_iter().next().unwrap())
+}
+
+/// An occurrence of an identifier.
+struct Occurrence {
+    locate: Locate,
+    /// Whether the occurrence declares the identifier.
+    declaration: bool,
+    /// Whether the occurrence is a declaration or the target of an assignment.
+    write: bool,
+    /// Whether the occurrence is an implicit port connection like `.clk`, naming the port too.
+    implicit: bool,
+}
+
+/// Collect identifiers in `scope` which name something of another scope.
+///
+/// They are the ports in explicit named port connections like `.clk(clk)`, members of selects like
+/// `a.b`, and the components of hierarchical identifiers like `u.x` after the first one.
+fn get_foreign_identifiers(scope: RefNode) -> Vec<Locate> {
+    let mut ret = Vec::new();
+    for node in scope {
+        match node {
+            RefNode::NamedPortConnectionIdentifier(x) if x.nodes.3.is_some() => {
+                ret.extend(get_identifier((&x.nodes.2).into()));
+            }
+            RefNode::MemberIdentifier(_) => ret.extend(get_identifier(node)),
+            RefNode::HierarchicalIdentifier(x) => {
+                let (ref root, ref list, ref last) = x.nodes;
+                if root.is_none() && list.is_empty() {
+                    continue;
+                }
+                let skip = if root.is_none() { 1 } else { 0 };
+                for (x, _, _) in list.iter().skip(skip) {
+                    ret.extend(get_identifier(x.into()));
+                }
+                ret.extend(get_identifier(last.into()));
+            }
+            _ => (),
+        }
+    }
+    ret
+}
+
+/// Collect the ports of implicit named port connections like `.clk` in `scope`.
+///
+/// They connect the identifier of the same name in `scope`.
+fn get_implicit_connections(scope: RefNode) -> Vec<Locate> {
+    let mut ret = Vec::new();
+    for node in scope {
+        if let RefNode::NamedPortConnectionIdentifier(x) = node {
+            if x.nodes.3.is_none() {
+                ret.extend(get_identifier((&x.nodes.2).into()));
+            }
+        }
+    }
+    ret
+}
+
+/// Collect identifiers declared in `scope`.
+fn get_declarations(scope: RefNode) -> Vec<Locate> {
+    let mut foreign = get_foreign_identifiers(scope.clone());
+    foreign.extend(get_implicit_connections(scope.clone()));
+    let mut ret = Vec::new();
+    for node in scope {
+        match node {
+            RefNode::NetDeclAssignment(_)
+            | RefNode::VariableDeclAssignment(_)
+            | RefNode::PortIdentifier(_)
+            | RefNode::ParamAssignment(_)
+            | RefNode::GenvarIdentifier(_) => {
+                if let Some(x) = get_identifier(node).filter(|x| !foreign.contains(x)) {
+                    ret.push(x);
+                }
+            }
+            _ => (),
+        }
+    }
+    ret
+}
+
+/// Collect occurrences of the identifier at byte offset `pos` within its enclosing scope.
+fn get_occurrences(syntax_tree: &SyntaxTree, pos: usize) -> Vec<Occurrence> {
+    let ident = match get_identifier_at(syntax_tree, pos) {
+        Some(x) => x,
+        None => return Vec::new(),
+    };
+    let name = syntax_tree.get_str(&ident);
+    let scope = get_scope(syntax_tree, pos);
+
+    let declarations = get_declarations(scope.clone());
+    let foreign = get_foreign_identifiers(scope.clone());
+    let implicit = get_implicit_connections(scope.clone());
+    let mut lvalues = Vec::new();
+    for node in scope.clone() {
+        if let RefNode::VariableLvalue(_) | RefNode::NetLvalue(_) = node {
+            if let Some(x) = get_identifier(node) {
+                lvalues.push(x);
+            }
+        }
+    }
+
+    let mut ret = Vec::new();
+    for node in scope {
+        let locate = match node {
+            RefNode::SimpleIdentifier(x) => x.nodes.0,
+            RefNode::EscapedIdentifier(x) => x.nodes.0,
+            _ => continue,
+        };
+        if get_local_offset(syntax_tree, &locate).is_some()
+            && syntax_tree.get_str(&locate) == name
+            && !foreign.contains(&locate)
+        {
+            let declaration = declarations.contains(&locate);
+            ret.push(Occurrence {
+                locate,
+                declaration,
+                write: declaration || lvalues.contains(&locate),
+                implicit: implicit.contains(&locate),
+            });
+        }
+    }
+    ret
+}
+
+/// Find references to the identifier at byte offset `pos` of the current document.
+pub fn references(
+    syntax_tree: &SyntaxTree,
+    uri: &Url,
+    s: &str,
+    pos: usize,
+    include_declaration: bool,
+) -> Vec<Location> {
+    get_occurrences(syntax_tree, pos)
+        .into_iter()
+        .filter(|x| include_declaration || !x.declaration)
+        .filter_map(|x| get_range(syntax_tree, s, RefNode::Locate(&x.locate)))
+        .map(|x| Location::new(uri.clone(), x))
+        .collect()
+}
+
+/// Highlight occurrences of the identifier at byte offset `pos` of the current document.
+///
+/// Declarations and assignment targets are `Write`, and the others are `Read`.
+pub fn document_highlights(
+    syntax_tree: &SyntaxTree,
+    s: &str,
+    pos: usize,
+) -> Vec<DocumentHighlight> {
+    get_occurrences(syntax_tree, pos)
+        .into_iter()
+        .filter_map(|x| {
+            let range = get_range(syntax_tree, s, RefNode::Locate(&x.locate))?;
+            let kind = if x.write {
+                DocumentHighlightKind::Write
+            } else {
+                DocumentHighlightKind::Read
+            };
+            Some(DocumentHighlight {
+                range,
+                kind: Some(kind),
+            })
+        })
+        .collect()
+}
+
+/// Get the range of the identifier at byte offset `pos` if it is declared in its enclosing scope.
+pub fn prepare_rename(syntax_tree: &SyntaxTree, s: &str, pos: usize) -> Option<Range> {
+    let occurrences = get_occurrences(syntax_tree, pos);
+    if !occurrences.iter().any(|x| x.declaration) {
+        return None;
+    }
+    let x = occurrences.iter().find(|x| {
+        get_local_offset(syntax_tree, &x.locate)
+            .is_some_and(|beg| beg <= pos && pos <= beg + x.locate.len)
+    })?;
+    get_range(syntax_tree, s, RefNode::Locate(&x.locate))
+}
+
+/// Rename the identifier at byte offset `pos` to `new_name` within its enclosing scope.
+///
+/// Implicit port connections like `.clk` are made explicit to keep connecting the port.
+/// Returns an error if `new_name` is not an identifier or is already declared in the scope.
+pub fn rename(
+    syntax_tree: &SyntaxTree,
+    uri: &Url,
+    s: &str,
+    pos: usize,
+    new_name: &str,
+) -> Result<Option<WorkspaceEdit>, String> {
+    if prepare_rename(syntax_tree, s, pos).is_none() {
+        return Ok(None);
+    }
+    if !is_identifier(new_name) {
+        return Err(format!("`{}` is not an identifier", new_name));
+    }
+    let declared = get_declarations(get_scope(syntax_tree, pos))
+        .iter()
+        .any(|x| syntax_tree.get_str(x) == Some(new_name));
+    if declared {
+        return Err(format!("`{}` is already declared", new_name));
+    }
+
+    let edits = get_occurrences(syntax_tree, pos)
+        .into_iter()
+        .filter_map(|x| {
+            let range = get_range(syntax_tree, s, RefNode::Locate(&x.locate))?;
+            let text = if x.implicit {
+                format!("{}({})", syntax_tree.get_str(&x.locate)?, new_name)
+            } else {
+                String::from(new_name)
+            };
+            Some(TextEdit::new(range, text))
+        })
+        .collect();
+    let mut changes = HashMap::new();
+    changes.insert(uri.clone(), edits);
+    Ok(Some(WorkspaceEdit::new(changes)))
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    let head = chars
+        .next()
+        .is_some_and(|x| x.is_ascii_alphabetic() || x == '_');
+    head && chars.all(|x| x.is_ascii_alphanumeric() || x == '_' || x == '$')
+        && !KEYWORDS.contains(&s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_references() {
+        let s = r##"module A (input logic a);
+  logic b;
+  assign b = a;
+endmodule
+module B;
+  logic b;
+  assign b = 1;
+endmodule
+"##;
+        let syntax_tree = crate::parse(s);
+        let uri = Url::parse("file:///test.sv").unwrap();
+        let lines = |include_declaration| {
+            let pos = s.find("b = a").unwrap();
+            references(&syntax_tree, &uri, s, pos, include_declaration)
+                .into_iter()
+                .map(|x| (x.range.start.line, x.range.start.character))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(lines(true), vec![(1, 8), (2, 9)]);
+        assert_eq!(lines(false), vec![(2, 9)]);
+        assert!(references(&syntax_tree, &uri, s, 0, true).is_empty());
+    }
+
+    #[test]
+    fn test_document_highlights() {
+        let s = r##"module A;
+  logic [1:0] a, b;
+  always_comb begin
+    a[b] = 0;
+    {b, a[0]} = a;
+  end
+endmodule
+"##;
+        let syntax_tree = crate::parse(s);
+        let highlights = |name: &str| {
+            let pos = s.find(name).unwrap();
+            document_highlights(&syntax_tree, s, pos)
+                .into_iter()
+                .map(|x| (x.range.start.line, x.range.start.character, x.kind.unwrap()))
+                .collect::<Vec<_>>()
+        };
+        use DocumentHighlightKind::{Read, Write};
+        assert_eq!(
+            highlights("a,"),
+            vec![(1, 14, Write), (3, 4, Write), (4, 8, Write), (4, 16, Read)]
+        );
+        assert_eq!(
+            highlights("b;"),
+            vec![(1, 17, Write), (3, 6, Read), (4, 5, Write)]
+        );
+    }
+
+    #[test]
+    fn test_rename() {
+        let s = r##"module A (input logic a);
+  logic b, c;
+  assign b = a;
+endmodule
+module B;
+  logic b;
+  assign b = 1;
+endmodule
+"##;
+        let syntax_tree = crate::parse(s);
+        let uri = Url::parse("file:///test.sv").unwrap();
+        let pos = s.find("b = a").unwrap();
+        assert_eq!(
+            prepare_rename(&syntax_tree, s, pos),
+            Some(Range::new(Position::new(2, 9), Position::new(2, 10)))
+        );
+        // Module names are not declared in the scope
+        assert_eq!(prepare_rename(&syntax_tree, s, s.find('A').unwrap()), None);
+
+        let edit = rename(&syntax_tree, &uri, s, pos, "d").unwrap().unwrap();
+        let edits = &edit.changes.unwrap()[&uri];
+        let edits: Vec<_> = edits
+            .iter()
+            .map(|x| {
+                (
+                    x.range.start.line,
+                    x.range.start.character,
+                    x.new_text.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(edits, vec![(1, 8, "d"), (2, 9, "d")]);
+
+        assert!(rename(&syntax_tree, &uri, s, pos, "c").is_err());
+        assert!(rename(&syntax_tree, &uri, s, pos, "a").is_err());
+        assert!(rename(&syntax_tree, &uri, s, pos, "1x").is_err());
+        assert!(rename(&syntax_tree, &uri, s, pos, "logic").is_err());
+    }
+
+    #[test]
+    fn test_rename_port_connection() {
+        let s = r##"module A (input logic clk, input logic rst);
+  sub u (.clk(clk), .en(u.x.en));
+endmodule
+"##;
+        let syntax_tree = crate::parse(s);
+        let uri = Url::parse("file:///test.sv").unwrap();
+        let pos = s.find("clk)").unwrap();
+        let edit = rename(&syntax_tree, &uri, s, pos, "clock")
+            .unwrap()
+            .unwrap();
+        let edits: Vec<_> = edit.changes.unwrap()[&uri]
+            .iter()
+            .map(|x| (x.range.start.line, x.range.start.character))
+            .collect();
+        // The port of `sub` is kept
+        assert_eq!(edits, vec![(0, 22), (1, 14)]);
+        // Ports of instances and hierarchical names don't collide
+        assert!(rename(&syntax_tree, &uri, s, pos, "en").is_ok());
+        assert!(rename(&syntax_tree, &uri, s, pos, "x").is_ok());
+        assert!(rename(&syntax_tree, &uri, s, pos, "rst").is_err());
+    }
+
+    #[test]
+    fn test_rename_implicit_port_connection() {
+        let s = r##"module A (input logic clk);
+  sub u (.clk);
+endmodule
+"##;
+        let syntax_tree = crate::parse(s);
+        let uri = Url::parse("file:///test.sv").unwrap();
+        let pos = s.find("clk").unwrap();
+        let edit = rename(&syntax_tree, &uri, s, pos, "clock")
+            .unwrap()
+            .unwrap();
+        let edits: Vec<_> = edit.changes.unwrap()[&uri]
+            .iter()
+            .map(|x| {
+                (
+                    x.range.start.line,
+                    x.range.start.character,
+                    x.new_text.clone(),
+                )
+            })
+            .collect();
+        // The port of `sub` is still connected to the renamed signal
+        assert_eq!(
+            edits,
+            vec![
+                (0, 22, String::from("clock")),
+                (1, 10, String::from("clk(clock)")),
+            ]
+        );
+    }
+}
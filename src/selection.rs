@@ -0,0 +1,104 @@
+use crate::position::get_position;
+use crate::symbol::get_local_offset;
+use sv_parser::{NodeEvent, RefNode, SyntaxTree};
+use tower_lsp::lsp_types::*;
+
+/// Get the nested selection ranges from the token at byte offset `pos` of the current document
+/// outward to the outermost declaration.
+pub fn selection_range(syntax_tree: &SyntaxTree, s: &str, pos: usize) -> SelectionRange {
+    // Spans of nodes containing `pos`, from the innermost
+    let mut spans: Vec<(usize, usize)> = Vec::new();
+    let mut stack: Vec<Option<(usize, usize)>> = Vec::new();
+    let mut skip = 0;
+    for event in syntax_tree.into_iter().event() {
+        match event {
+            NodeEvent::Enter(node) => {
+                let span = match node {
+                    RefNode::WhiteSpace(_) => {
+                        skip += 1;
+                        None
+                    }
+                    RefNode::Locate(x) if skip == 0 => {
+                        get_local_offset(syntax_tree, x).map(|beg| (beg, beg + x.len))
+                    }
+                    _ => None,
+                };
+                stack.push(span);
+            }
+            NodeEvent::Leave(node) => {
+                if let RefNode::WhiteSpace(_) = node {
+                    skip -= 1;
+                }
+                let (beg, end) = match stack.pop().flatten() {
+                    Some(x) => x,
+                    None => continue,
+                };
+                let nested = spans
+                    .last()
+                    .is_none_or(|x| beg <= x.0 && x.1 <= end && *x != (beg, end));
+                if beg <= pos && pos <= end && nested {
+                    spans.push((beg, end));
+                }
+                if let Some(parent) = stack.last_mut() {
+                    *parent = Some(match *parent {
+                        Some((x, y)) => (x.min(beg), y.max(end)),
+                        None => (beg, end),
+                    });
+                }
+            }
+        }
+    }
+
+    let range = |beg, end| {
+        let (line, col) = get_position(s, beg);
+        let (end_line, end_col) = get_position(s, end);
+        Range::new(Position::new(line, col), Position::new(end_line, end_col))
+    };
+    let mut ret: Option<SelectionRange> = None;
+    for (beg, end) in spans.into_iter().rev() {
+        ret = Some(SelectionRange {
+            range: range(beg, end),
+            parent: ret.map(Box::new),
+        });
+    }
+    ret.unwrap_or_else(|| SelectionRange {
+        range: range(pos, pos),
+        parent: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selection_range() {
+        let s = "module A;\n  assign a = b + c; // comment\nendmodule\n";
+        let syntax_tree = crate::parse(s);
+        let mut ranges = Vec::new();
+        let mut x = Some(selection_range(&syntax_tree, s, s.find('b').unwrap()));
+        while let Some(selection) = x {
+            let range = selection.range;
+            ranges.push((
+                range.start.line,
+                range.start.character,
+                range.end.line,
+                range.end.character,
+            ));
+            x = selection.parent.map(|x| *x);
+        }
+        assert_eq!(ranges.first(), Some(&(1, 13, 1, 14)));
+        assert!(ranges.contains(&(1, 13, 1, 18)));
+        assert!(ranges.contains(&(1, 9, 1, 18)));
+        assert!(ranges.contains(&(1, 2, 1, 19)));
+        assert_eq!(ranges.last(), Some(&(0, 0, 2, 9)));
+        for x in ranges.windows(2) {
+            assert!((x[1].0, x[1].1) <= (x[0].0, x[0].1));
+            assert!((x[0].2, x[0].3) <= (x[1].2, x[1].3));
+            assert_ne!(x[0], x[1]);
+        }
+
+        let selection = selection_range(&syntax_tree, s, s.len());
+        assert!(selection.parent.is_none());
+    }
+}
@@ -0,0 +1,173 @@
+use crate::symbol::{get_identifier, get_local_offset};
+use sv_parser::{NodeEvent, RefNode, SyntaxTree};
+use tower_lsp::lsp_types::*;
+
+/// Token types of the legend, indexed by `SemanticToken::token_type`.
+pub const TOKEN_TYPES: &[SemanticTokenType] = &[
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::TYPE,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::PARAMETER,
+    SemanticTokenType::MACRO,
+    SemanticTokenType::NAMESPACE,
+    SemanticTokenType::INTERFACE,
+    SemanticTokenType::CLASS,
+    SemanticTokenType::FUNCTION,
+];
+
+const KEYWORD: u32 = 0;
+const TYPE: u32 = 1;
+const VARIABLE: u32 = 2;
+const PARAMETER: u32 = 3;
+const MACRO: u32 = 4;
+const NAMESPACE: u32 = 5;
+const INTERFACE: u32 = 6;
+const CLASS: u32 = 7;
+const FUNCTION: u32 = 8;
+
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: Vec::new(),
+    }
+}
+
+/// Get the token type of identifiers and keywords inside `node`.
+fn get_token_type(node: &RefNode) -> Option<u32> {
+    let ret = match node {
+        RefNode::IntegerVectorType(_)
+        | RefNode::IntegerAtomType(_)
+        | RefNode::NonIntegerType(_)
+        | RefNode::NetType(_)
+        | RefNode::TypeIdentifier(_) => TYPE,
+        RefNode::ParameterIdentifier(_) => PARAMETER,
+        RefNode::TextMacroIdentifier(_) => MACRO,
+        RefNode::ModuleIdentifier(_)
+        | RefNode::PackageIdentifier(_)
+        | RefNode::ProgramIdentifier(_) => NAMESPACE,
+        RefNode::InterfaceIdentifier(_) => INTERFACE,
+        RefNode::ClassIdentifier(_) => CLASS,
+        RefNode::FunctionIdentifier(_) | RefNode::TaskIdentifier(_) | RefNode::TfIdentifier(_) => {
+            FUNCTION
+        }
+        _ => return None,
+    };
+    Some(ret)
+}
+
+/// Collect semantic tokens of the current document, delta-encoded in the order of positions.
+pub fn semantic_tokens(syntax_tree: &SyntaxTree, s: &str) -> Vec<SemanticToken> {
+    let mut parameters = Vec::new();
+    for node in syntax_tree {
+        if let RefNode::ParameterIdentifier(_) = node {
+            if let Some(name) = get_identifier(node).and_then(|x| syntax_tree.get_str(&x)) {
+                parameters.push(name);
+            }
+        }
+    }
+
+    let mut tokens = Vec::new();
+    let mut stack = Vec::new();
+    for event in syntax_tree.into_iter().event() {
+        match event {
+            NodeEvent::Enter(node) => {
+                let (locate, token_type) = match node {
+                    RefNode::Keyword(x) => (x.nodes.0, stack.last().copied().unwrap_or(KEYWORD)),
+                    RefNode::SimpleIdentifier(x) => {
+                        // Usages of parameters can be parsed as other kinds of identifiers
+                        let parameter = syntax_tree
+                            .get_str(&x.nodes.0)
+                            .is_some_and(|x| parameters.contains(&x));
+                        let token_type = match stack.last() {
+                            _ if parameter => PARAMETER,
+                            Some(x) => *x,
+                            None => VARIABLE,
+                        };
+                        (x.nodes.0, token_type)
+                    }
+                    RefNode::EscapedIdentifier(x) => {
+                        (x.nodes.0, stack.last().copied().unwrap_or(VARIABLE))
+                    }
+                    _ => {
+                        if let Some(x) = get_token_type(&node) {
+                            stack.push(x);
+                        }
+                        continue;
+                    }
+                };
+                if let Some(beg) = get_local_offset(syntax_tree, &locate) {
+                    tokens.push((beg, locate.len, token_type));
+                }
+            }
+            NodeEvent::Leave(node) => {
+                if get_token_type(&node).is_some() {
+                    stack.pop();
+                }
+            }
+        }
+    }
+    tokens.sort_by_key(|(beg, _, _)| *beg);
+
+    let mut ret = Vec::new();
+    let (mut offset, mut line, mut col) = (0, 0, 0);
+    let (mut prev_line, mut prev_col) = (0, 0);
+    for (beg, len, token_type) in tokens {
+        // Skip overlapping tokens, which come from macro expansions
+        if beg < offset {
+            continue;
+        }
+        for c in s[offset..beg].chars() {
+            if c == '\n' {
+                line += 1;
+                col = 0;
+            } else {
+                col += c.len_utf16() as u32;
+            }
+        }
+        let length = s[beg..beg + len].encode_utf16().count() as u32;
+        ret.push(SemanticToken {
+            delta_line: line - prev_line,
+            delta_start: if line == prev_line {
+                col - prev_col
+            } else {
+                col
+            },
+            length,
+            token_type,
+            token_modifiers_bitset: 0,
+        });
+        offset = beg + len;
+        prev_line = line;
+        prev_col = col;
+        col += length;
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_semantic_tokens() {
+        let s = "module A #(parameter W = 1);\n  logic [W-1:0] a;\nendmodule\n";
+        let syntax_tree = crate::parse(s);
+        let tokens: Vec<_> = semantic_tokens(&syntax_tree, s)
+            .into_iter()
+            .map(|x| (x.delta_line, x.delta_start, x.length, x.token_type))
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                (0, 0, 6, KEYWORD),
+                (0, 7, 1, NAMESPACE),
+                (0, 4, 9, KEYWORD),
+                (0, 10, 1, PARAMETER),
+                (1, 2, 5, TYPE),
+                (0, 7, 1, PARAMETER),
+                (0, 7, 1, VARIABLE),
+                (1, 0, 9, KEYWORD),
+            ]
+        );
+    }
+}
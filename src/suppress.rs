@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+/// Line ranges in which lint rules are suppressed by `// svlint off` and `// svlint on` comments.
+///
+/// `// svlint off` without rule names suppresses all rules until `// svlint on`.
+#[derive(Debug, Default)]
+pub struct Suppression {
+    regions: Vec<(Option<String>, u32, u32)>,
+}
+
+impl Suppression {
+    pub fn new(s: &str) -> Self {
+        let mut regions = Vec::new();
+        let mut opened: HashMap<Option<String>, u32> = HashMap::new();
+        for (line, text) in s.split('\n').enumerate() {
+            let line = line as u32;
+            let (off, rules) = match get_marker(text) {
+                Some(x) => x,
+                None => continue,
+            };
+            if off {
+                for rule in rules {
+                    opened.entry(rule).or_insert(line);
+                }
+            } else if rules == [None] {
+                for (rule, beg) in opened.drain() {
+                    regions.push((rule, beg, line));
+                }
+            } else {
+                for rule in rules {
+                    if let Some(beg) = opened.remove(&rule) {
+                        regions.push((rule, beg, line));
+                    }
+                }
+            }
+        }
+        for (rule, beg) in opened {
+            regions.push((rule, beg, u32::MAX));
+        }
+        Suppression { regions }
+    }
+
+    pub fn is_suppressed(&self, rule: &str, line: u32) -> bool {
+        self.regions.iter().any(|(x, beg, end)| {
+            x.as_deref().is_none_or(|x| x == rule) && *beg <= line && line <= *end
+        })
+    }
+}
+
+/// Get whether the marker in `text` is `off`, and the rule names of it.
+///
+/// `None` in the rule names means all rules.
+fn get_marker(text: &str) -> Option<(bool, Vec<Option<String>>)> {
+    let comment = if let Some(x) = text.find("//") {
+        &text[x + 2..]
+    } else if let Some(x) = text.find("/*") {
+        let comment = &text[x + 2..];
+        &comment[..comment.find("*/")?]
+    } else {
+        return None;
+    };
+
+    let mut words = comment
+        .split(|x: char| x.is_whitespace() || x == ',')
+        .filter(|x| !x.is_empty());
+    if words.next()? != "svlint" {
+        return None;
+    }
+    let off = match words.next()? {
+        "off" => true,
+        "on" => false,
+        _ => return None,
+    };
+    let mut rules: Vec<_> = words.map(|x| Some(String::from(x))).collect();
+    if rules.is_empty() {
+        rules.push(None);
+    }
+    Some((off, rules))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suppression() {
+        let s = r##"module A;
+  // svlint off legacy_always
+  always @* begin
+  end
+  // svlint on legacy_always
+  /* svlint off */
+  always @* begin
+  end
+  // svlint on
+  // svlint off re_forbidden_port, legacy_always
+endmodule
+"##;
+        let suppression = Suppression::new(s);
+        assert!(!suppression.is_suppressed("legacy_always", 0));
+        assert!(suppression.is_suppressed("legacy_always", 2));
+        assert!(!suppression.is_suppressed("enum_with_type", 2));
+        assert!(!suppression.is_suppressed("enum_with_type", 4));
+        assert!(suppression.is_suppressed("enum_with_type", 6));
+        assert!(!suppression.is_suppressed("enum_with_type", 9));
+        assert!(suppression.is_suppressed("re_forbidden_port", 10));
+        assert!(suppression.is_suppressed("legacy_always", 10));
+    }
+}
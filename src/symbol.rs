@@ -0,0 +1,482 @@
+use crate::message::{self, Message};
+use crate::position::get_position;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use sv_parser::{unwrap_node, Locate, NodeEvent, RefNode, SyntaxTree};
+use tower_lsp::lsp_types::*;
+
+/// Get the range of `node` in the current document, excluding trailing whitespace.
+///
+/// Returns `None` if the node originates from an included file or a macro expansion.
+pub fn get_range(syntax_tree: &SyntaxTree, s: &str, node: RefNode) -> Option<Range> {
+    let (beg, end) = get_span(syntax_tree, node)?;
+    let (line, col) = get_position(s, beg);
+    let (end_line, end_col) = get_position(s, end);
+    Some(Range::new(
+        Position::new(line, col),
+        Position::new(end_line, end_col),
+    ))
+}
+
+/// Get the byte offsets of the beginning and the end of `node` in the current document.
+pub fn get_span(syntax_tree: &SyntaxTree, node: RefNode) -> Option<(usize, usize)> {
+    let mut beg = None;
+    let mut end = None;
+    let mut skip = 0;
+    for event in node.into_iter().event() {
+        match event {
+            NodeEvent::Enter(RefNode::WhiteSpace(_)) => skip += 1,
+            NodeEvent::Leave(RefNode::WhiteSpace(_)) => skip -= 1,
+            NodeEvent::Enter(RefNode::Locate(x)) if skip == 0 => {
+                if beg.is_none() {
+                    beg = Some(*x);
+                }
+                end = Some(*x);
+            }
+            _ => (),
+        }
+    }
+    let beg = get_local_offset(syntax_tree, &beg?)?;
+    let end = end?;
+    let end = get_local_offset(syntax_tree, &end)? + end.len;
+    Some((beg, end))
+}
+
+/// Map `locate` back to a byte offset in the current document.
+pub fn get_local_offset(syntax_tree: &SyntaxTree, locate: &Locate) -> Option<usize> {
+    match syntax_tree.get_origin(locate) {
+        Some((path, pos)) if path.as_os_str().is_empty() => Some(pos),
+        _ => None,
+    }
+}
+
+/// Resolve `locate` to a location in the current document, or in the included file it
+/// originates from.
+pub fn get_location(
+    syntax_tree: &SyntaxTree,
+    uri: &Url,
+    s: &str,
+    locate: &Locate,
+) -> Option<Location> {
+    let (path, beg) = syntax_tree.get_origin(locate)?;
+    let end = beg + locate.len;
+    let range = |s: &str| {
+        let (line, col) = get_position(s, beg);
+        let (end_line, end_col) = get_position(s, end);
+        Range::new(Position::new(line, col), Position::new(end_line, end_col))
+    };
+    if path.as_os_str().is_empty() {
+        Some(Location::new(uri.clone(), range(s)))
+    } else {
+        let text = fs::read_to_string(path).ok()?;
+        let uri = get_file_uri(path)?;
+        Some(Location::new(uri, range(&text)))
+    }
+}
+
+/// Convert the path of an included file to its URI.
+pub fn get_file_uri(path: &Path) -> Option<Url> {
+    Url::from_file_path(fs::canonicalize(path).ok()?).ok()
+}
+
+/// Get the `Locate` of the first identifier in `node`.
+pub fn get_identifier(node: RefNode) -> Option<Locate> {
+    match unwrap_node!(node, SimpleIdentifier, EscapedIdentifier) {
+        Some(RefNode::SimpleIdentifier(x)) => Some(x.nodes.0),
+        Some(RefNode::EscapedIdentifier(x)) => Some(x.nodes.0),
+        _ => None,
+    }
+}
+
+fn get_symbol_identifier(node: &RefNode) -> Option<(SymbolKind, Option<Locate>)> {
+    let (kind, ident) = match node {
+        RefNode::ModuleDeclaration(_) => (
+            SymbolKind::Module,
+            unwrap_node!(node.clone(), ModuleIdentifier),
+        ),
+        RefNode::InterfaceDeclaration(_) => (
+            SymbolKind::Interface,
+            unwrap_node!(node.clone(), InterfaceIdentifier),
+        ),
+        RefNode::ProgramDeclaration(_) => (
+            SymbolKind::Module,
+            unwrap_node!(node.clone(), ProgramIdentifier),
+        ),
+        RefNode::PackageDeclaration(_) => (
+            SymbolKind::Package,
+            unwrap_node!(node.clone(), PackageIdentifier),
+        ),
+        RefNode::ClassDeclaration(_) => (
+            SymbolKind::Class,
+            unwrap_node!(node.clone(), ClassIdentifier),
+        ),
+        RefNode::FunctionDeclaration(_) => (
+            SymbolKind::Function,
+            unwrap_node!(node.clone(), FunctionIdentifier),
+        ),
+        RefNode::TaskDeclaration(_) => (
+            SymbolKind::Function,
+            unwrap_node!(node.clone(), TaskIdentifier),
+        ),
+        _ => return None,
+    };
+    Some((kind, ident.and_then(get_identifier)))
+}
+
+/// Keywords which open a construct, and the keywords closing it.
+const PAIRED_KEYWORDS: &[(&str, &str)] = &[
+    ("begin", "end"),
+    ("case", "endcase"),
+    ("casex", "endcase"),
+    ("casez", "endcase"),
+    ("class", "endclass"),
+    ("fork", "join"),
+    ("function", "endfunction"),
+    ("generate", "endgenerate"),
+    ("interface", "endinterface"),
+    ("module", "endmodule"),
+    ("package", "endpackage"),
+    ("program", "endprogram"),
+    ("task", "endtask"),
+];
+
+/// Find the keyword closing the construct opened by the keyword at byte offset `beg` of `path`.
+///
+/// `node` is searched, and the origin and the length of the closing keyword are returned.
+pub fn get_closing_keyword(
+    syntax_tree: &SyntaxTree,
+    node: RefNode,
+    path: &Path,
+    beg: usize,
+) -> Option<(usize, usize)> {
+    let mut open = None;
+    let mut depth = 0;
+    for x in node {
+        let locate = match x {
+            RefNode::Keyword(x) => x.nodes.0,
+            _ => continue,
+        };
+        let (x_path, x_beg) = syntax_tree.get_origin(&locate)?;
+        if x_path != path {
+            continue;
+        }
+        let keyword = syntax_tree.get_str(&locate)?;
+        match open {
+            None if x_beg == beg => {
+                let (_, close) = PAIRED_KEYWORDS.iter().find(|(x, _)| *x == keyword)?;
+                open = Some((keyword, *close));
+                depth = 1;
+            }
+            Some((opening, closing)) => {
+                if keyword == opening {
+                    depth += 1;
+                } else if keyword == closing {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some((x_beg, locate.len));
+                    }
+                }
+            }
+            None => (),
+        }
+    }
+    None
+}
+
+/// Collect module, interface, program, package, class, function and task declarations.
+///
+/// Functions and tasks are nested under their enclosing declaration.
+pub fn document_symbols(syntax_tree: &SyntaxTree, s: &str) -> Vec<DocumentSymbol> {
+    let mut ret = Vec::new();
+    let mut stack: Vec<Option<DocumentSymbol>> = Vec::new();
+
+    for event in syntax_tree.into_iter().event() {
+        match event {
+            NodeEvent::Enter(node) => {
+                if let Some((kind, ident)) = get_symbol_identifier(&node) {
+                    let symbol = ident.and_then(|ident| {
+                        let range = get_range(syntax_tree, s, node.clone())?;
+                        let selection_range = get_range(syntax_tree, s, RefNode::Locate(&ident))?;
+                        #[allow(deprecated)]
+                        Some(DocumentSymbol {
+                            name: String::from(syntax_tree.get_str(&ident)?),
+                            detail: None,
+                            kind,
+                            tags: None,
+                            deprecated: None,
+                            range,
+                            selection_range,
+                            children: None,
+                        })
+                    });
+                    stack.push(symbol);
+                }
+            }
+            NodeEvent::Leave(node) => {
+                if get_symbol_identifier(&node).is_some() {
+                    if let Some(symbol) = stack.pop().flatten() {
+                        match stack.iter_mut().rev().flatten().next() {
+                            Some(parent) => {
+                                parent.children.get_or_insert_with(Vec::new).push(symbol)
+                            }
+                            None => ret.push(symbol),
+                        }
+                    }
+                }
+            }
+        }
+    }
+    ret
+}
+
+/// Collect top-level declarations of the current document for the workspace symbol index.
+pub fn workspace_symbols(syntax_tree: &SyntaxTree, uri: &Url, s: &str) -> Vec<SymbolInformation> {
+    document_symbols(syntax_tree, s)
+        .into_iter()
+        .map(|x| {
+            #[allow(deprecated)]
+            SymbolInformation {
+                name: x.name,
+                kind: x.kind,
+                tags: None,
+                deprecated: None,
+                location: Location::new(uri.clone(), x.selection_range),
+                container_name: None,
+            }
+        })
+        .collect()
+}
+
+/// Report modules and packages of `symbols` which are also declared by the other files of `index`.
+///
+/// The related information of each diagnostic points at the other declarations.
+pub fn duplicate_diagnostics(
+    symbols: &[SymbolInformation],
+    index: &HashMap<Url, Vec<SymbolInformation>>,
+    locale: Option<&str>,
+) -> Vec<Diagnostic> {
+    let mut ret = Vec::new();
+    for x in symbols {
+        if x.kind != SymbolKind::Module && x.kind != SymbolKind::Package {
+            continue;
+        }
+        let mut others: Vec<_> = index
+            .iter()
+            .filter(|(uri, _)| **uri != x.location.uri)
+            .flat_map(|(_, symbols)| symbols)
+            .filter(|y| y.kind == x.kind && y.name == x.name)
+            .map(|y| y.location.clone())
+            .collect();
+        if others.is_empty() {
+            continue;
+        }
+        others.sort_by(|a, b| a.uri.as_str().cmp(b.uri.as_str()));
+        let duplicate = if x.kind == SymbolKind::Module {
+            Message::DuplicateModule
+        } else {
+            Message::DuplicatePackage
+        };
+        let related = others
+            .into_iter()
+            .map(|location| DiagnosticRelatedInformation {
+                location,
+                message: message::get(locale, Message::OtherDeclaration, &[&x.name]),
+            })
+            .collect();
+        ret.push(Diagnostic::new(
+            x.location.range,
+            Some(DiagnosticSeverity::Error),
+            None,
+            Some(String::from("svls")),
+            message::get(locale, duplicate, &[&x.name]),
+            Some(related),
+            None,
+        ));
+    }
+    ret
+}
+
+/// Check whether the characters of `query` appear in `name` in order, ignoring case.
+pub fn is_fuzzy_match(query: &str, name: &str) -> bool {
+    let mut name = name.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|x| name.any(|y| x == y))
+}
+
+/// Find the declaration of the module instantiated at byte offset `pos` of the current document.
+pub fn goto_module_definition(
+    syntax_tree: &SyntaxTree,
+    uri: &Url,
+    s: &str,
+    pos: usize,
+) -> Option<Location> {
+    let mut name = None;
+    for node in syntax_tree {
+        if let RefNode::ModuleInstantiation(x) = node {
+            if let Some(ident) = get_identifier((&x.nodes.0).into()) {
+                if let Some(beg) = get_local_offset(syntax_tree, &ident) {
+                    if beg <= pos && pos <= beg + ident.len {
+                        name = syntax_tree.get_str(&ident);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    let name = name?;
+
+    for node in syntax_tree {
+        let ident = match node {
+            RefNode::ModuleDeclaration(_) => unwrap_node!(node, ModuleIdentifier),
+            RefNode::InterfaceDeclaration(_) => unwrap_node!(node, InterfaceIdentifier),
+            _ => continue,
+        };
+        if let Some(ident) = ident.and_then(get_identifier) {
+            if syntax_tree.get_str(&ident) == Some(name) {
+                return get_location(syntax_tree, uri, s, &ident);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_document_symbols() {
+        let s = "module A;\n  function void f();\n  endfunction\n  task t;\n  endtask\nendmodule\npackage P;\nendpackage\n";
+        let syntax_tree = crate::parse(s);
+        let symbols = document_symbols(&syntax_tree, s);
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(symbols[0].name, "A");
+        assert_eq!(symbols[0].kind, SymbolKind::Module);
+        assert_eq!(
+            symbols[0].range,
+            Range::new(Position::new(0, 0), Position::new(5, 9))
+        );
+        assert_eq!(
+            symbols[0].selection_range,
+            Range::new(Position::new(0, 7), Position::new(0, 8))
+        );
+        let children = symbols[0].children.as_ref().unwrap();
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0].name, "f");
+        assert_eq!(children[1].name, "t");
+        assert_eq!(symbols[1].name, "P");
+        assert_eq!(symbols[1].kind, SymbolKind::Package);
+    }
+
+    #[test]
+    fn test_workspace_symbols() {
+        let s = "module A;\n  function void f();\n  endfunction\nendmodule\ninterface I;\nendinterface\n";
+        let syntax_tree = crate::parse(s);
+        let uri = Url::parse("file:///test.sv").unwrap();
+        let symbols: Vec<_> = workspace_symbols(&syntax_tree, &uri, s)
+            .into_iter()
+            .map(|x| (x.name, x.kind, x.location.range.start.line))
+            .collect();
+        assert_eq!(
+            symbols,
+            vec![
+                (String::from("A"), SymbolKind::Module, 0),
+                (String::from("I"), SymbolKind::Interface, 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_diagnostics() {
+        let symbols = |uri: &Url, s: &str| {
+            let syntax_tree = crate::parse(s);
+            workspace_symbols(&syntax_tree, uri, s)
+        };
+        let a = Url::parse("file:///a.sv").unwrap();
+        let b = Url::parse("file:///b.sv").unwrap();
+        let mut index = HashMap::new();
+        index.insert(a.clone(), symbols(&a, "module A;\nendmodule\n"));
+        index.insert(
+            b.clone(),
+            symbols(&b, "\nmodule A;\nendmodule\npackage A;\nendpackage\n"),
+        );
+
+        let diags = duplicate_diagnostics(&index[&a], &index, None);
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].message, "Module `A` is declared in multiple files");
+        assert_eq!(diags[0].range.start, Position::new(0, 7));
+        let related = diags[0].related_information.as_ref().unwrap();
+        assert_eq!(related.len(), 1);
+        assert_eq!(related[0].location.uri, b);
+        assert_eq!(related[0].location.range.start, Position::new(1, 7));
+
+        // The package doesn't collide with the module
+        let diags = duplicate_diagnostics(&index[&b], &index, None);
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].range.start, Position::new(1, 7));
+    }
+
+    #[test]
+    fn test_is_fuzzy_match() {
+        assert!(is_fuzzy_match("", "top"));
+        assert!(is_fuzzy_match("axi", "AXI_Master"));
+        assert!(is_fuzzy_match("axm", "AXI_Master"));
+        assert!(!is_fuzzy_match("mxa", "AXI_Master"));
+        assert!(!is_fuzzy_match("tops", "top"));
+    }
+
+    #[test]
+    fn test_goto_module_definition() {
+        let s = "module A;\nendmodule\nmodule B;\n  A u_a ();\nendmodule\n";
+        let syntax_tree = crate::parse(s);
+        let uri = Url::parse("file:///test.sv").unwrap();
+        let location = goto_module_definition(&syntax_tree, &uri, s, 33).unwrap();
+        assert_eq!(location.uri, uri);
+        assert_eq!(
+            location.range,
+            Range::new(Position::new(0, 7), Position::new(0, 8))
+        );
+        assert!(goto_module_definition(&syntax_tree, &uri, s, 36).is_none());
+    }
+
+    #[test]
+    fn test_get_closing_keyword() {
+        let s = r##"module A;
+  always_comb begin
+    case (a)
+      0: begin
+        case (b)
+          0: c = 0;
+        endcase
+      end
+    endcase
+  end
+endmodule
+"##;
+        let syntax_tree = crate::parse(s);
+        let node = syntax_tree.into_iter().next().unwrap();
+        let closing = |keyword: &str| {
+            let beg = s.find(keyword).unwrap();
+            get_closing_keyword(&syntax_tree, node.clone(), Path::new(""), beg)
+                .map(|(beg, len)| (beg, &s[beg..beg + len]))
+        };
+        assert_eq!(
+            closing("module"),
+            Some((s.rfind("endmodule").unwrap(), "endmodule"))
+        );
+        assert_eq!(closing("begin"), Some((s.rfind("end\n").unwrap(), "end")));
+        assert_eq!(
+            closing("case (a)"),
+            Some((s.rfind("endcase").unwrap(), "endcase"))
+        );
+        assert_eq!(
+            closing("case (b)"),
+            Some((s.find("endcase").unwrap(), "endcase"))
+        );
+        assert_eq!(closing("always_comb"), None);
+    }
+}